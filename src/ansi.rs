@@ -0,0 +1,244 @@
+use crate::model::{Appearance, Color, Style};
+use crate::span::{Span, Spans};
+use crate::strip::{read_escape_sequence, ESCAPE};
+
+/// Reads text with ANSI escape sequences back into spans, like a terminal would display them.
+///
+/// The appearance carries over from one call to the next, for the text that comes line by line.
+/// The escape sequences other than the colors and styles are left out.
+pub struct AnsiParser {
+    appearance: Appearance,
+}
+
+impl Default for AnsiParser {
+    fn default() -> Self {
+        AnsiParser { appearance: Appearance::plain() }
+    }
+}
+
+impl AnsiParser {
+    pub fn parse(&mut self, text: &str) -> Vec<Span> {
+        let mut spans = Vec::new();
+        let mut current = String::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c != ESCAPE {
+                current.push(c);
+                continue;
+            }
+
+            if let Some((parameters, 'm')) = read_escape_sequence(&mut chars) {
+                let mut next = self.appearance.clone();
+                apply_sgr(&mut next, &parameters);
+
+                if next != self.appearance && !current.is_empty() {
+                    spans.push(Span { text: std::mem::take(&mut current), appearance: self.appearance.clone() });
+                }
+                self.appearance = next;
+            }
+        }
+
+        if !current.is_empty() {
+            spans.push(Span { text: current, appearance: self.appearance.clone() });
+        }
+
+        spans
+    }
+}
+
+/// The spans of a text with ANSI escape sequences, for the outputs that start from them.
+pub fn parse_ansi(text: &str) -> Spans {
+    let mut parser = AnsiParser::default();
+    let spans = parser.parse(text);
+
+    Spans { spans, end: parser.appearance }
+}
+
+/// Applies the parameters of a "Select Graphic Rendition" sequence, like `1;31` or `4:3`.
+fn apply_sgr(appearance: &mut Appearance, parameters: &str) {
+    let parameters: Vec<&str> = parameters.split(';').collect();
+    let mut i = 0;
+
+    while i < parameters.len() {
+        let mut parts = parameters[i].split(':');
+        // an empty parameter is a 0, like in `\e[m`
+        let code = match parts.next().unwrap_or("") {
+            "" => Some(0),
+            it => it.parse::<u8>().ok(),
+        };
+        let sub_parameters: Vec<&str> = parts.collect();
+
+        if code == Some(0) {
+            *appearance = Appearance::plain();
+            i += 1;
+            continue;
+        }
+
+        let styles = &mut appearance.styles;
+        let colors = &mut appearance.colors;
+
+        match code {
+            Some(1) => add(styles, Style::Strong),
+            Some(2) => add(styles, Style::Dim),
+            Some(3) => add(styles, Style::Italic),
+            Some(4) => {
+                styles.retain(|it| !it.is_underline());
+                match sub_parameters.first().copied() {
+                    Some("0") => {}
+                    Some("2") => styles.push(Style::DoubleUnderline),
+                    Some("3") => styles.push(Style::CurlyUnderline),
+                    Some("4") => styles.push(Style::DottedUnderline),
+                    Some("5") => styles.push(Style::DashedUnderline),
+                    _ => styles.push(Style::Underline),
+                }
+            }
+            Some(5) => add(styles, Style::Blink),
+            Some(6) => add(styles, Style::RapidBlink),
+            Some(7) => add(styles, Style::Reversed),
+            Some(8) => add(styles, Style::Hidden),
+            Some(9) => add(styles, Style::CrossedOut),
+            Some(10) => styles.retain(|it| !matches!(it, Style::AlternativeFont(_))),
+            Some(font @ 11..=19) => {
+                styles.retain(|it| !matches!(it, Style::AlternativeFont(_)));
+                styles.push(Style::AlternativeFont(font - 10));
+            }
+            Some(20) => add(styles, Style::Fraktur),
+            Some(21) => {
+                styles.retain(|it| !it.is_underline());
+                styles.push(Style::DoublyUnderlined);
+            }
+            Some(22) => styles.retain(|it| !matches!(it, Style::Strong | Style::Dim)),
+            Some(23) => styles.retain(|it| !matches!(it, Style::Italic | Style::Fraktur)),
+            Some(24) => styles.retain(|it| !it.is_underline()),
+            Some(25) => styles.retain(|it| !matches!(it, Style::Blink | Style::RapidBlink)),
+            Some(27) => styles.retain(|it| *it != Style::Reversed),
+            Some(28) => styles.retain(|it| *it != Style::Hidden),
+            Some(29) => styles.retain(|it| *it != Style::CrossedOut),
+            Some(code @ 30..=37) => colors.foreground = Some(Color::palette(code - 30)),
+            Some(39) => colors.foreground = None,
+            Some(code @ 40..=47) => colors.background = Some(Color::palette(code - 40)),
+            Some(49) => colors.background = None,
+            Some(51) => add(styles, Style::Framed),
+            Some(52) => add(styles, Style::Encircled),
+            Some(53) => add(styles, Style::Overline),
+            Some(54) => styles.retain(|it| !matches!(it, Style::Framed | Style::Encircled)),
+            Some(55) => styles.retain(|it| *it != Style::Overline),
+            Some(59) => colors.underline = None,
+            Some(73) => {
+                styles.retain(|it| *it != Style::Subscript);
+                add(styles, Style::Superscript);
+            }
+            Some(74) => {
+                styles.retain(|it| *it != Style::Superscript);
+                add(styles, Style::Subscript);
+            }
+            Some(75) => styles.retain(|it| !matches!(it, Style::Superscript | Style::Subscript)),
+            Some(code @ 90..=97) => colors.foreground = Some(Color::palette(code - 90 + 8)),
+            Some(code @ 100..=107) => colors.background = Some(Color::palette(code - 100 + 8)),
+            Some(layer @ (38 | 48 | 58)) => {
+                // either `38:5:n` in a single parameter or `38;5;n` over the next ones
+                let color = if sub_parameters.is_empty() {
+                    let (color, used) = extended_color(&parameters[i + 1..], false);
+                    i += used;
+                    color
+                } else {
+                    extended_color(&sub_parameters, true).0
+                };
+
+                if let Some(color) = color {
+                    match layer {
+                        38 => colors.foreground = Some(color),
+                        48 => colors.background = Some(color),
+                        _ => colors.underline = Some(color),
+                    }
+                }
+            }
+            // the unknown codes change nothing
+            _ => {}
+        }
+
+        i += 1;
+    }
+}
+
+/// The color of `5;n` or `2;r;g;b`, with the number of parameters that it takes.
+///
+/// The sub-parameters may have a color space before the red, like `2::r:g:b`.
+fn extended_color(arguments: &[&str], sub_parameters: bool) -> (Option<Color>, usize) {
+    let number = |index: usize| arguments.get(index).and_then(|it| it.parse::<u8>().ok());
+
+    match arguments.first().copied() {
+        Some("5") => (number(1).map(Color::palette), 2),
+        Some("2") => {
+            let start = if sub_parameters && arguments.len() > 4 { 2 } else { 1 };
+            let color = match (number(start), number(start + 1), number(start + 2)) {
+                (Some(red), Some(green), Some(blue)) => Some(Color::rgb(red, green, blue)),
+                _ => None,
+            };
+            (color, 4)
+        }
+        _ => (None, 0),
+    }
+}
+
+fn add(styles: &mut Vec<Style>, style: Style) {
+    if !styles.contains(&style) {
+        styles.push(style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::{parse_ansi, AnsiParser};
+    use crate::model::{Appearance, Color, Colors, Style};
+    use crate::model::Style::{CurlyUnderline, Dim, Italic, Strong, Underline};
+    use crate::span::Span;
+
+    fn span(text: &str, colors: Colors, styles: &[Style]) -> Span {
+        Span { text: text.to_string(), appearance: Appearance::plain().with(&colors, styles) }
+    }
+
+    #[test]
+    fn read_the_colors_and_styles() {
+        assert_eq!(
+            parse_ansi("\x1b[1;31merror:\x1b[0m disk \x1b[38;2;1;2;3;48;5;208mfull\x1b[m").spans,
+            vec!(
+                span("error:", Colors::new_fg(Color::red()), &[Strong]),
+                span(" disk ", Colors::none(), &[]),
+                span("full", Colors::new(Color::rgb(1, 2, 3), Color::palette(208)), &[]),
+            )
+        );
+    }
+
+    #[test]
+    fn turn_off_the_styles_one_by_one() {
+        assert_eq!(
+            parse_ansi("\x1b[1;2;3ma\x1b[22mb\x1b[92;23;4:3;58:2::9:8:7mc").spans,
+            vec!(
+                span("a", Colors::none(), &[Strong, Dim, Italic]),
+                span("b", Colors::none(), &[Italic]),
+                span("c", Colors { foreground: Some(Color::bright_green()), background: None, underline: Some(Color::rgb(9, 8, 7)) }, &[CurlyUnderline]),
+            )
+        );
+    }
+
+    #[test]
+    fn merge_the_text_that_looks_the_same_and_skip_the_other_sequences() {
+        assert_eq!(
+            parse_ansi("\x1b[4ma\x1b[4m\x1b]8;;https://example.com\x07b\x1b[2Kc\x1b[0m").spans,
+            vec!(span("abc", Colors::none(), &[Underline]))
+        );
+    }
+
+    #[test]
+    fn carry_the_appearance_over_to_the_next_text() {
+        let mut parser = AnsiParser::default();
+        parser.parse("\x1b[34mblue\n");
+
+        assert_eq!(
+            parser.parse("still blue\x1b[0m\n"),
+            vec!(span("still blue", Colors::new_fg(Color::blue()), &[]), span("\n", Colors::none(), &[]))
+        );
+    }
+}
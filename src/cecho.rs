@@ -1,18 +1,45 @@
+use crate::check::mixed_references;
+use crate::config::{ColorDepth, Config, Output};
+use crate::debug::render_debug;
+use crate::discord::render_discord;
+use crate::html::render_html;
+use crate::irc::render_irc;
+use crate::json::render_json;
+use crate::pango::{render_i3bar, render_pango};
+use crate::polybar::render_polybar;
+use crate::prompt::render_prompt;
+use crate::svg::render_svg;
+use crate::tmux::{passthrough, render_tmux};
+use crate::model::Part;
 use crate::model::Part::Specification;
+use crate::model::Text;
 use crate::parser::parse_format;
-use crate::writer::spec_to_ansi;
+use crate::span::resolve;
+use crate::writer::render;
 
 pub fn cecho(inputs: Vec<String>) -> Result<String, String> {
+    cecho_with_config(inputs, &Config::default())
+}
+
+pub fn cecho_with_config(inputs: Vec<String>, config: &Config) -> Result<String, String> {
+    // the newline comes after everything, the escape sequences included, whichever way the output is made
+    format_with_config(inputs, config).map(|mut it| {
+        if config.newline {
+            it.push('\n');
+        }
+        it
+    })
+}
+
+fn format_with_config(inputs: Vec<String>, config: &Config) -> Result<String, String> {
     let parsed = parse_format(&inputs[0]);
 
     match parsed {
         Err(m) => Err(m.to_string()),
         Ok(specs) => {
-            let has_specifiers = specs.iter().any(|it|
-                match it {
-                    Specification { text: _, color: _, styles: _ } => true,
-                    _ => false
-                });
+            // the quoted literals don't take any argument
+            let has_specifiers = specs.iter().any(|it| matches!(it, Specification { text, .. } if !matches!(text, Text::Literal(_))));
+            let mixed = if config.strict { mixed_references(&specs) } else { None };
 
             // TODO Special cases handling for more user friendliness
             //
@@ -30,14 +57,36 @@ pub fn cecho(inputs: Vec<String>) -> Result<String, String> {
             //
             // The other cases require at least 2 arguments
 
-            if inputs.len() < 2 && (has_specifiers || inputs[0].is_empty()) {
+            if let Some(conflict) = mixed {
+                Err(conflict)
+            } else if inputs.len() < 2 && (has_specifiers || inputs[0].is_empty()) {
                 Err("The minimum number of arguments is 2. The first argument is the format. If no formatting is necessary, use an empty string.".to_string())
-            } else if inputs[0].is_empty() {
-                let mut result = inputs[1].to_string();
-                inputs.iter().skip(2).for_each(|s| result.push_str(s));
-                Ok(result)
             } else {
-                spec_to_ansi(&inputs, specs)
+                // the arguments of an empty format are a plain text, that each output escapes like any other
+                let specs = if inputs[0].is_empty() { vec!(Part::literal(&inputs[1..].concat())) } else { specs };
+                // the colors depend on the terminal only when writing to it
+                let resolving = match config.output {
+                    Output::Ansi => config.clone(),
+                    // the debug output shows what the terminal gets, also when it's written elsewhere
+                    Output::Debug => Config { colored: true, ..config.clone() },
+                    _ => Config { depth: ColorDepth::TrueColor, colored: true, ..config.clone() },
+                };
+                let parts = specs.clone();
+
+                resolve(&inputs, specs, &resolving).map(|spans| match config.output {
+                    Output::Ansi => passthrough(&render(&spans), config.passthrough),
+                    Output::Html => render_html(&spans),
+                    Output::Svg => render_svg(&spans),
+                    Output::Json => render_json(&spans),
+                    Output::Pango => render_pango(&spans),
+                    Output::I3bar => render_i3bar(&spans),
+                    Output::Polybar => render_polybar(&spans),
+                    Output::Prompt(shell) => passthrough(&render_prompt(&spans, shell), config.passthrough),
+                    Output::Tmux => render_tmux(&spans),
+                    Output::Irc => render_irc(&spans),
+                    Output::Discord => render_discord(&spans),
+                    Output::Debug => render_debug(&parts, &spans, &resolving),
+                })
             }
         }
     }
@@ -47,7 +96,8 @@ pub fn cecho(inputs: Vec<String>) -> Result<String, String> {
 #[cfg(test)]
 mod tests {
     use crate::vecs;
-    use crate::cecho::cecho;
+    use crate::cecho::{cecho, cecho_with_config};
+    use crate::config::{ColorDepth, Config, Output, Passthrough, Shell};
 
     #[test]
     fn check_that_there_is_at_least_2_arguments_when_there_is_1_spec() {
@@ -63,7 +113,7 @@ mod tests {
     #[test]
     fn print_formatted_string_with_positional_arguments() {
         let actual = cecho(vecs!("{}+{}={}", "1", "2", "3"));
-        assert_eq!(actual.unwrap(), "1+2=3\x1b[0m".to_string());
+        assert_eq!(actual.unwrap(), "1+2=3".to_string());
     }
 
     #[test]
@@ -87,35 +137,35 @@ mod tests {
             "this will be ignored because the format contains no formatting specifier"
         );
         let actual = cecho(i);
-        assert_eq!(actual.ok(), Some("Just raw text, nothing special, no placeholder like {}\x1b[0m".to_string()));
+        assert_eq!(actual.ok(), Some("Just raw text, nothing special, no placeholder like {}".to_string()));
     }
 
     #[test]
     fn when_a_format_is_specified_then_use_it_2_specs() {
         let i = vecs!("{} and {}", "A", "B");
         let actual = cecho(i);
-        assert_eq!(actual.ok(), Some("A and B\x1b[0m".to_string()));
+        assert_eq!(actual.ok(), Some("A and B".to_string()));
     }
 
     #[test]
     fn print_red() {
         let i = vecs!("{#r}", "red");
         let actual = cecho(i);
-        assert_eq!(actual.ok(), Some("\x1b[31mred\x1b[0m\x1b[0m".to_string()));
+        assert_eq!(actual.ok(), Some("\x1b[31mred\x1b[0m".to_string()));
     }
 
     #[test]
     fn print_green() {
         let i = vecs!("{#g}", "green");
         let actual = cecho(i);
-        assert_eq!(actual.ok(), Some("\x1b[32mgreen\x1b[0m\x1b[0m".to_string()));
+        assert_eq!(actual.ok(), Some("\x1b[32mgreen\x1b[0m".to_string()));
     }
 
     #[test]
     fn tolerate_missing_arguments_when_the_format_doesnt_contain_specs() {
         let i = vecs!(r#"\{}"#);
         let actual = cecho(i);
-        assert_eq!(actual.ok(), Some("{}\x1b[0m".to_string()));
+        assert_eq!(actual.ok(), Some("{}".to_string()));
     }
 
     #[test]
@@ -125,7 +175,130 @@ mod tests {
         assert_eq!(actual.ok(), None);
     }
 
-    // TODO detect invalid cases:
-    // {garbage value}
-    // TODO refuse to mix positional, indexed and named, only 1 of each
+    #[test]
+    fn scopes_only_take_the_arguments_of_their_placeholders() {
+        let actual = cecho(vecs!("{#red}error: {!bold}{}{/!bold} happened{/}", "disk full"));
+        assert_eq!(actual.unwrap(), "\x1b[31merror: \x1b[1mdisk full\x1b[22m happened\x1b[0m");
+    }
+
+    #[test]
+    fn quoted_literals_need_no_argument() {
+        let actual = cecho(vecs!(r#"{"done" #g}"#));
+        assert_eq!(actual.unwrap(), "\x1b[32mdone\x1b[0m");
+    }
+
+    #[test]
+    fn end_with_a_newline_on_demand() {
+        let config = Config { newline: true, ..Config::default() };
+
+        assert_eq!(cecho_with_config(vecs!("{#g}", "done"), &config), Ok("\x1b[32mdone\x1b[0m\n".to_string()));
+        assert_eq!(cecho_with_config(vecs!("", "raw", "text"), &config), Ok("rawtext\n".to_string()));
+    }
+
+    #[test]
+    fn output_only_the_text_without_colors() {
+        let config = Config { colored: false, ..Config::default() };
+        let actual = cecho_with_config(vecs!("{#red}error:{/} {\"disk\" !bold} {}\t", "full"), &config);
+        assert_eq!(actual, Ok("error: disk full\t".to_string()));
+    }
+
+    #[test]
+    fn output_html_whatever_the_terminal() {
+        let config = Config { output: Output::Html, colored: false, depth: ColorDepth::Monochrome, ..Config::default() };
+        let actual = cecho_with_config(vecs!("{#red}<{}>{/}", "b"), &config);
+        assert_eq!(actual, Ok("<span style=\"color:#cd0000\">&lt;b&gt;</span>".to_string()));
+    }
+
+    #[test]
+    fn pass_the_hyperlinks_through_tmux() {
+        let config = Config { passthrough: Passthrough::Tmux, ..Config::default() };
+        let actual = cecho_with_config(vecs!("\\e]8;;{}\\a{!bold}\\e]8;;\\a", "https://example.com", "link"), &config);
+        assert_eq!(
+            actual,
+            Ok("\x1bPtmux;\x1b\x1b]8;;https://example.com\x07\x1b\\\x1b[1mlink\x1b[0m\x1bPtmux;\x1b\x1b]8;;\x07\x1b\\".to_string())
+        );
+    }
+
+    #[test]
+    fn pass_the_hyperlinks_of_the_prompts_through_tmux() {
+        let config = Config { output: Output::Prompt(Shell::Bash), passthrough: Passthrough::Tmux, ..Config::default() };
+        let actual = cecho_with_config(vecs!("\\e]8;;{}\\a{!bold}\\e]8;;\\a", "https://example.com", "link"), &config);
+        assert_eq!(
+            actual,
+            Ok("\x1bPtmux;\x1b\x1b]8;;https://example.com\x07\x1b\\\x01\x1b[1m\x02link\x01\x1b[0m\x02\x1bPtmux;\x1b\x1b]8;;\x07\x1b\\".to_string())
+        );
+    }
+
+    #[test]
+    fn refuse_to_mix_positional_and_indexed_specifiers_unless_lenient() {
+        let lenient = Config { strict: false, ..Config::default() };
+
+        assert_eq!(
+            cecho(vecs!("{} {%1}", "a")),
+            Err("The specifier #1 {} is positional while the specifier #2 {%1} is indexed, a format can't mix them (CECHO_STRICT=0 allows it)".to_string())
+        );
+        assert_eq!(cecho_with_config(vecs!("{} {%1}", "a"), &lenient), Ok("a a".to_string()));
+    }
+
+    fn empty_format(output: Output, text: &str) -> String {
+        cecho_with_config(vecs!("", text), &Config { output, ..Config::default() }).unwrap()
+    }
+
+    #[test]
+    fn escape_the_arguments_of_an_empty_format_in_html() {
+        assert_eq!(empty_format(Output::Html, "<b>\"x\"&"), "&lt;b&gt;&quot;x&quot;&amp;");
+    }
+
+    #[test]
+    fn escape_the_arguments_of_an_empty_format_in_svg() {
+        assert!(empty_format(Output::Svg, "<b>\"x\"&").contains(">&lt;b&gt;&quot;x&quot;&amp;</text>"));
+    }
+
+    #[test]
+    fn escape_the_arguments_of_an_empty_format_in_json() {
+        assert_eq!(
+            empty_format(Output::Json, "\"x\"\\"),
+            r#"[{"text":"\"x\"\\","foreground":null,"background":null,"underline":null,"styles":[]}]"#
+        );
+    }
+
+    #[test]
+    fn escape_the_arguments_of_an_empty_format_in_pango() {
+        assert_eq!(empty_format(Output::Pango, "<b>&"), "&lt;b&gt;&amp;");
+    }
+
+    #[test]
+    fn escape_the_arguments_of_an_empty_format_in_i3bar() {
+        assert_eq!(empty_format(Output::I3bar, "<b>\""), r#"{"full_text":"&lt;b&gt;&quot;","markup":"pango"}"#);
+    }
+
+    #[test]
+    fn escape_the_arguments_of_an_empty_format_in_polybar() {
+        assert_eq!(empty_format(Output::Polybar, "100%"), "100%%");
+    }
+
+    #[test]
+    fn escape_the_arguments_of_an_empty_format_in_tmux() {
+        assert_eq!(empty_format(Output::Tmux, "#1"), "##1");
+    }
+
+    #[test]
+    fn escape_the_arguments_of_an_empty_format_in_zsh() {
+        assert_eq!(empty_format(Output::Prompt(Shell::Zsh), "100%"), "100%%");
+    }
+
+    #[test]
+    fn escape_the_arguments_of_an_empty_format_in_discord() {
+        assert_eq!(empty_format(Output::Discord, "a```b"), "```ansi\na`\u{200b}``b\n```");
+    }
+
+    #[test]
+    fn refuse_the_words_that_a_specifier_cant_interpret() {
+        assert_eq!(
+            cecho(vecs!("{garbage value}", "a")),
+            Err("Don't know how to interpret 'garbage' in the specifier 'garbage value'".to_string())
+        );
+    }
+
+    // TODO refuse to mix the named specifiers with the others, once there are some
 }
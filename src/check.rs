@@ -0,0 +1,198 @@
+use crate::config::Config;
+use crate::json::style_name;
+use crate::model::{Colors, Part, Style, Text};
+use crate::parser::parse_format_with_all_errors;
+
+/// Checks the formats without any argument, like in a pre-commit hook.
+///
+/// Fails with the report of all the formats that have an error or a warning.
+pub fn check_formats(formats: &[String], config: &Config) -> Result<String, String> {
+    if formats.is_empty() {
+        return Err("The check mode takes the formats to check as its arguments".to_string());
+    }
+
+    let report: Vec<String> = formats.iter()
+        .flat_map(|format| check_format(format, config).into_iter().map(move |it| format!("'{}': {}", format, it)))
+        .collect();
+
+    if report.is_empty() {
+        Ok(String::new())
+    } else {
+        Err(report.join("\n"))
+    }
+}
+
+/// The errors of the format, then the warnings about what it would display.
+///
+/// The errors of all the specifiers are reported, the other problems are only found once the format is parsed.
+pub fn check_format(format: &str, config: &Config) -> Vec<String> {
+    let parts = match parse_format_with_all_errors(format) {
+        Err(errors) => return errors.iter().map(|it| format!("error: {}", it)).collect(),
+        Ok(parts) => parts,
+    };
+
+    let mut problems = Vec::new();
+
+    match mixed_references(&parts) {
+        Some(conflict) if config.strict => problems.push(format!("error: {}", conflict)),
+        Some(_) => problems.push("warning: mixes positional {} and indexed {%n} specifiers, the positions are hard to follow".to_string()),
+        None => {}
+    }
+
+    for (number, part) in specifiers(&parts) {
+        // the closing tags repeat the colors and styles of their opening specifier
+        if let Part::Specification { color, styles, .. } | Part::Open { color, styles } = part {
+            problems.extend(check_specifier(color, styles).into_iter().map(|it| format!("warning: the specifier #{} {}", number, it)));
+        }
+    }
+
+    problems
+}
+
+/// Tells which specifiers conflict when the format has both positional and indexed specifiers.
+///
+/// The positional ones take the arguments in order whatever the indexed ones take, which is rarely what was meant.
+pub fn mixed_references(parts: &[Part]) -> Option<String> {
+    let mut positional = Vec::new();
+    let mut indexed = Vec::new();
+
+    for (number, part) in specifiers(parts) {
+        match part {
+            Part::Specification { text: Text::Positional, .. } => positional.push(format!("#{} {{}}", number)),
+            Part::Specification { text: Text::Indexed(index), .. } => indexed.push(format!("#{} {{%{}}}", number, index)),
+            _ => {}
+        }
+    }
+
+    if positional.is_empty() || indexed.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "{} positional while {} indexed, a format can't mix them (CECHO_STRICT=0 allows it)",
+        subject(&positional, "The specifier", "The specifiers"),
+        subject(&indexed, "the specifier", "the specifiers"),
+    ))
+}
+
+/// The parts between braces with their number in the format, starting at 1.
+fn specifiers(parts: &[Part]) -> impl Iterator<Item = (usize, &Part)> {
+    parts.iter()
+        .filter(|it| !matches!(it, Part::Literal(_)))
+        .enumerate()
+        .map(|(i, it)| (i + 1, it))
+}
+
+/// Like `the specifier #1 {} is` or `the specifiers #1 {} and #3 {} are`.
+fn subject(specifiers: &[String], singular: &str, plural: &str) -> String {
+    match specifiers {
+        [only] => format!("{} {} is", singular, only),
+        [first @ .., last] => format!("{} {} and {} are", plural, first.join(", "), last),
+        [] => String::new(),
+    }
+}
+
+fn check_specifier(colors: &Colors, styles: &[Style]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut seen: Vec<&Style> = Vec::new();
+
+    for style in styles {
+        if seen.contains(&style) {
+            warnings.push(format!("has the style {} more than once", style_name(style)));
+        } else {
+            seen.push(style);
+        }
+    }
+
+    if styles.contains(&Style::Strong) && styles.contains(&Style::Dim) {
+        warnings.push("is both bold and dim, many terminals only display one of them".to_string());
+    }
+
+    if colors.foreground.is_some() && colors.foreground == colors.background && !styles.contains(&Style::Hidden) {
+        warnings.push("has the same foreground and background colors, its text can't be seen".to_string());
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::check::{check_format, check_formats};
+    use crate::config::Config;
+    use crate::vecs;
+
+    fn check(format: &str) -> Vec<String> {
+        check_format(format, &Config::default())
+    }
+
+    #[test]
+    fn report_the_error_of_a_format() {
+        assert_eq!(check("{#nope}"), vec!("error: Don't know how to interpret the color 'nope'"));
+        assert_eq!(check("{foo bar}"), vec!("error: Don't know how to interpret 'foo' in the specifier 'foo bar'"));
+    }
+
+    #[test]
+    fn report_the_errors_of_all_the_specifiers() {
+        assert_eq!(
+            check("{#nope} \\q {x=1} {#r}"),
+            vec!(
+                "error: Don't know how to interpret the color 'nope'",
+                "error: Invalid escape sequence: \\q",
+                "error: Don't know how to interpret the keyword 'x' as a mode",
+            )
+        );
+        assert_eq!(
+            check("{#nope} {!bold {#r}"),
+            vec!("error: Don't know how to interpret the color 'nope'", "error: Can't nest specifiers")
+        );
+    }
+
+    #[test]
+    fn warn_about_what_the_format_would_display() {
+        let lenient = Config { strict: false, ..Config::default() };
+
+        assert_eq!(
+            check_format("{!ss #red/red} {%1} {!s!dim}text{/}", &lenient),
+            vec!(
+                "warning: mixes positional {} and indexed {%n} specifiers, the positions are hard to follow",
+                "warning: the specifier #1 has the style bold more than once",
+                "warning: the specifier #1 has the same foreground and background colors, its text can't be seen",
+                "warning: the specifier #3 is both bold and dim, many terminals only display one of them",
+            )
+        );
+    }
+
+    #[test]
+    fn a_valid_format_has_no_problem() {
+        assert_eq!(check("{#red!bold}error:{/} {} {\"done\" #g}"), Vec::<String>::new());
+        assert_eq!(check("{#k/k!hidden}"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn tell_which_specifiers_mix_positional_and_indexed() {
+        assert_eq!(
+            check("{} {#r}{/} {%2} {#g}"),
+            vec!("error: The specifiers #1 {} and #5 {} are positional while the specifier #4 {%2} is indexed, a format can't mix them (CECHO_STRICT=0 allows it)")
+        );
+        assert_eq!(
+            check("{%1} {} {%3} {%2}"),
+            vec!("error: The specifier #2 {} is positional while the specifiers #1 {%1}, #3 {%3} and #4 {%2} are indexed, a format can't mix them (CECHO_STRICT=0 allows it)")
+        );
+    }
+
+    #[test]
+    fn report_the_problems_of_all_the_formats() {
+        let invalid = vecs!("{}", "a\\q", "{!bold", "{x=1}");
+        let valid = vecs!("{}", "{#r}");
+
+        assert_eq!(
+            check_formats(&invalid, &Config::default()),
+            Err([
+                "'a\\q': error: Invalid escape sequence: \\q",
+                "'{!bold': error: The specifiers are imbalanced: missing }",
+                "'{x=1}': error: Don't know how to interpret the keyword 'x' as a mode",
+            ].join("\n"))
+        );
+        assert_eq!(check_formats(&valid, &Config::default()), Ok(String::new()));
+    }
+}
@@ -0,0 +1,239 @@
+// The sRGB components are f64 between 0 and 1.
+// They may go out of that range for the colors that are outside the gamut.
+
+// CIE Lab constants
+const KAPPA: f64 = 24389.0 / 27.0;
+const EPSILON: f64 = 216.0 / 24389.0;
+const D50_WHITE: [f64; 3] = [0.3457 / 0.3585, 1.0, (1.0 - 0.3457 - 0.3585) / 0.3585];
+
+const D65_WHITE: [f64; 3] = [0.3127 / 0.3290, 1.0, (1.0 - 0.3127 - 0.3290) / 0.3290];
+
+const LINEAR_SRGB_TO_XYZ: [[f64; 3]; 3] = [
+    [0.41239079926595934, 0.357584339383878, 0.1804807884018343],
+    [0.21263900587151027, 0.715168678767756, 0.07219231536073371],
+    [0.01933081871559182, 0.11919477979462598, 0.9505321522496607],
+];
+
+const D50_TO_D65: [[f64; 3]; 3] = [
+    [0.955473421488075, -0.02309845494876471, 0.06325924320057072],
+    [-0.0283697093338637, 1.0099953980813041, 0.021041441191917323],
+    [0.012314014864481998, -0.020507649298898964, 1.330365926242124],
+];
+
+const XYZ_TO_LINEAR_SRGB: [[f64; 3]; 3] = [
+    [3.2409699419045226, -1.537383177570094, -0.4986107602930034],
+    [-0.9692436362808796, 1.8759675015077202, 0.04155505740717559],
+    [0.05563007969699366, -0.20397695888897652, 1.0569715142428786],
+];
+
+/// Converts a hue in degrees, a saturation and a lightness between 0 and 1 to sRGB.
+pub fn hsl_to_srgb(hue: f64, saturation: f64, lightness: f64) -> [f64; 3] {
+    let hue = hue.rem_euclid(360.0);
+    let f = |n: f64| {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = saturation * lightness.min(1.0 - lightness);
+        lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+
+    [f(0.0), f(8.0), f(4.0)]
+}
+
+/// Converts sRGB to a hue in degrees, a saturation and a lightness between 0 and 1.
+pub fn srgb_to_hsl(srgb: [f64; 3]) -> [f64; 3] {
+    let [red, green, blue] = srgb;
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let lightness = (max + min) / 2.0;
+    let delta = max - min;
+
+    if delta == 0.0 {
+        return [0.0, 0.0, lightness];
+    }
+
+    let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = if max == red {
+        60.0 * ((green - blue) / delta).rem_euclid(6.0)
+    } else if max == green {
+        60.0 * ((blue - red) / delta + 2.0)
+    } else {
+        60.0 * ((red - green) / delta + 4.0)
+    };
+
+    [hue, saturation, lightness]
+}
+
+/// Converts a hue in degrees, a whiteness and a blackness between 0 and 1 to sRGB.
+pub fn hwb_to_srgb(hue: f64, whiteness: f64, blackness: f64) -> [f64; 3] {
+    if whiteness + blackness >= 1.0 {
+        let gray = whiteness / (whiteness + blackness);
+        return [gray, gray, gray];
+    }
+
+    hsl_to_srgb(hue, 1.0, 0.5).map(|it| it * (1.0 - whiteness - blackness) + whiteness)
+}
+
+/// Converts a CIE Lab color (D50 white point, like in CSS) to sRGB.
+pub fn lab_to_srgb(lightness: f64, a: f64, b: f64) -> [f64; 3] {
+    let f1 = (lightness + 16.0) / 116.0;
+    let f0 = a / 500.0 + f1;
+    let f2 = f1 - b / 200.0;
+
+    let x = if f0.powi(3) > EPSILON { f0.powi(3) } else { (116.0 * f0 - 16.0) / KAPPA };
+    let y = if lightness > KAPPA * EPSILON { f1.powi(3) } else { lightness / KAPPA };
+    let z = if f2.powi(3) > EPSILON { f2.powi(3) } else { (116.0 * f2 - 16.0) / KAPPA };
+
+    let d50 = [x * D50_WHITE[0], y * D50_WHITE[1], z * D50_WHITE[2]];
+    let d65 = multiply(&D50_TO_D65, d50);
+
+    multiply(&XYZ_TO_LINEAR_SRGB, d65).map(gamma_encode)
+}
+
+/// Converts sRGB to CIE Lab with the D65 white point of sRGB.
+pub fn srgb_to_lab(srgb: [f64; 3]) -> [f64; 3] {
+    let xyz = multiply(&LINEAR_SRGB_TO_XYZ, srgb.map(gamma_decode));
+    let f = |t: f64| if t > EPSILON { t.cbrt() } else { (KAPPA * t + 16.0) / 116.0 };
+    let [fx, fy, fz] = [0, 1, 2].map(|i| f(xyz[i] / D65_WHITE[i]));
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// The CIEDE2000 color difference between 2 CIE Lab colors.
+pub fn ciede2000(first: [f64; 3], second: [f64; 3]) -> f64 {
+    let [l1, a1, b1] = first;
+    let [l2, a2, b2] = second;
+    let pow7 = |it: f64| it.powi(7);
+    let cosd = |it: f64| it.to_radians().cos();
+
+    let c_bar = (a1.hypot(b1) + a2.hypot(b2)) / 2.0;
+    let g = 0.5 * (1.0 - (pow7(c_bar) / (pow7(c_bar) + pow7(25.0))).sqrt());
+    let a1 = (1.0 + g) * a1;
+    let a2 = (1.0 + g) * a2;
+    let c1 = a1.hypot(b1);
+    let c2 = a2.hypot(b2);
+    let hue = |a: f64, b: f64| if a == 0.0 && b == 0.0 { 0.0 } else { b.atan2(a).to_degrees().rem_euclid(360.0) };
+    let h1 = hue(a1, b1);
+    let h2 = hue(a2, b2);
+
+    let delta_l = l2 - l1;
+    let delta_c = c2 - c1;
+    let delta_h = if c1 * c2 == 0.0 {
+        0.0
+    } else if h2 - h1 > 180.0 {
+        h2 - h1 - 360.0
+    } else if h2 - h1 < -180.0 {
+        h2 - h1 + 360.0
+    } else {
+        h2 - h1
+    };
+    let delta_big_h = 2.0 * (c1 * c2).sqrt() * (delta_h / 2.0).to_radians().sin();
+
+    let l_bar = (l1 + l2) / 2.0;
+    let c_bar = (c1 + c2) / 2.0;
+    let h_bar = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+
+    let t = 1.0 - 0.17 * cosd(h_bar - 30.0) + 0.24 * cosd(2.0 * h_bar)
+        + 0.32 * cosd(3.0 * h_bar + 6.0) - 0.20 * cosd(4.0 * h_bar - 63.0);
+    let delta_theta = 30.0 * (-((h_bar - 275.0) / 25.0).powi(2)).exp();
+    let r_c = 2.0 * (pow7(c_bar) / (pow7(c_bar) + pow7(25.0))).sqrt();
+    let s_l = 1.0 + 0.015 * (l_bar - 50.0).powi(2) / (20.0 + (l_bar - 50.0).powi(2)).sqrt();
+    let s_c = 1.0 + 0.045 * c_bar;
+    let s_h = 1.0 + 0.015 * c_bar * t;
+    let r_t = -(2.0 * delta_theta).to_radians().sin() * r_c;
+
+    ((delta_l / s_l).powi(2)
+        + (delta_c / s_c).powi(2)
+        + (delta_big_h / s_h).powi(2)
+        + r_t * (delta_c / s_c) * (delta_big_h / s_h)
+    ).sqrt()
+}
+
+/// Converts a CIE LCh color (D50 white point) to sRGB.
+pub fn lch_to_srgb(lightness: f64, chroma: f64, hue: f64) -> [f64; 3] {
+    let (a, b) = polar_to_cartesian(chroma, hue);
+    lab_to_srgb(lightness, a, b)
+}
+
+/// Converts an Oklab color to sRGB.
+pub fn oklab_to_srgb(lightness: f64, a: f64, b: f64) -> [f64; 3] {
+    let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ].map(gamma_encode)
+}
+
+/// Converts an Oklch color to sRGB.
+pub fn oklch_to_srgb(lightness: f64, chroma: f64, hue: f64) -> [f64; 3] {
+    let (a, b) = polar_to_cartesian(chroma, hue);
+    oklab_to_srgb(lightness, a, b)
+}
+
+pub fn bytes_to_srgb(bytes: [u8; 3]) -> [f64; 3] {
+    bytes.map(|it| it as f64 / 255.0)
+}
+
+/// Brings the sRGB components back to bytes, clipping the colors that are out of the gamut.
+pub fn srgb_to_bytes(srgb: [f64; 3]) -> [u8; 3] {
+    srgb.map(|it| (it.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+fn polar_to_cartesian(chroma: f64, hue: f64) -> (f64, f64) {
+    let radians = hue.to_radians();
+    (chroma * radians.cos(), chroma * radians.sin())
+}
+
+fn gamma_encode(linear: f64) -> f64 {
+    if linear.abs() <= 0.0031308 {
+        12.92 * linear
+    } else {
+        linear.signum() * (1.055 * linear.abs().powf(1.0 / 2.4) - 0.055)
+    }
+}
+
+fn gamma_decode(encoded: f64) -> f64 {
+    if encoded.abs() <= 0.04045 {
+        encoded / 12.92
+    } else {
+        encoded.signum() * ((encoded.abs() + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn multiply(matrix: &[[f64; 3]; 3], vector: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color_space::{ciede2000, srgb_to_lab};
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-4, "{} is not close to {}", actual, expected);
+    }
+
+    #[test]
+    fn ciede2000_matches_the_reference_data() {
+        // from Sharma, Wu and Dalal's test data
+        assert_close(ciede2000([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485]), 2.0425);
+        assert_close(ciede2000([50.0, -1.0, 2.0], [50.0, 0.0, 0.0]), 2.3669);
+        assert_close(ciede2000([2.0776, 0.0795, -1.1350], [0.9033, -0.0636, -0.5514]), 0.9082);
+    }
+
+    #[test]
+    fn white_is_at_the_top_of_the_lab_lightness() {
+        let [l, a, b] = srgb_to_lab([1.0, 1.0, 1.0]);
+        assert_close(l, 100.0);
+        assert!(a.abs() < 1e-3 && b.abs() < 1e-3);
+    }
+}
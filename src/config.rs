@@ -0,0 +1,411 @@
+use std::env;
+use std::io::{IsTerminal, stdout};
+
+/// How many colors the terminal can display.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum ColorDepth {
+    TrueColor,
+    Palette256,
+    Ansi16,
+    Monochrome,
+}
+
+/// Whether to output colors.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum ColorPolicy {
+    /// Only when the output is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+/// How the formatted text is written.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Output {
+    /// With the ANSI escape sequences of the terminals
+    Ansi,
+    /// HTML markup with inline CSS, for web pages and reports
+    Html,
+    /// An image of a terminal, for screenshots in the docs
+    Svg,
+    /// The text, colors and styles of each span, for other programs
+    Json,
+    /// Pango markup, for the status bars like i3blocks and waybar
+    Pango,
+    /// The i3bar protocol's JSON blocks with Pango markup, for i3bar and swaybar
+    I3bar,
+    /// Polybar's format tags
+    Polybar,
+    /// The escape sequences with the markers that a shell prompt needs
+    Prompt(Shell),
+    /// Tmux's style sequences, for its status line
+    Tmux,
+    /// mIRC's formatting codes, for the IRC messages
+    Irc,
+    /// A code block with ANSI highlighting, for the Discord messages
+    Discord,
+    /// The parsed format and the visible escape sequences, for the bug reports
+    Debug,
+}
+
+/// The shells whose prompts need their own markers around the escape sequences.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    /// Zsh's own prompt sequences, like `%F{red}`, where possible
+    ZshNative,
+    Fish,
+}
+
+/// Which terminal multiplexer must pass the operating system commands to the terminal.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Passthrough {
+    Off,
+    Tmux,
+    Screen,
+}
+
+/// What cecho does.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Mode {
+    /// Formats the arguments
+    Format,
+    /// Removes the ANSI escape sequences from the standard input
+    Strip,
+    /// Converts the ANSI escape sequences of the standard input to cecho formats
+    Reverse,
+    /// Reports the errors and warnings of the formats, without any argument
+    Check,
+}
+
+/// The settings that don't belong to the format.
+///
+/// They come from the environment rather than from options, to keep the format the first argument.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Config {
+    pub depth: ColorDepth,
+    /// When false, no escape sequence is output at all, only the text.
+    pub colored: bool,
+    /// When true, every specifier keeps its colors and styles until another one changes them.
+    pub preserve: bool,
+    /// When true, the output ends with a newline, like `echo`.
+    pub newline: bool,
+    pub output: Output,
+    pub mode: Mode,
+    pub passthrough: Passthrough,
+    /// When true, a format can't mix positional and indexed specifiers.
+    pub strict: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            depth: ColorDepth::TrueColor,
+            colored: true,
+            preserve: false,
+            newline: false,
+            output: Output::Ansi,
+            mode: Mode::Format,
+            passthrough: Passthrough::Off,
+            strict: true,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, String> {
+        Config::from_lookup(|name| env::var(name).ok(), stdout().is_terminal())
+    }
+
+    pub fn from_lookup<F>(lookup: F, is_terminal: bool) -> Result<Self, String>
+        where F: Fn(&str) -> Option<String>
+    {
+        let colored = match color_policy(&lookup)? {
+            ColorPolicy::Auto => is_terminal,
+            ColorPolicy::Always => true,
+            ColorPolicy::Never => false,
+        };
+
+        Ok(Config {
+            depth: detect_depth(&lookup)?,
+            colored,
+            preserve: flag(&lookup, "CECHO_PRESERVE", false)?,
+            newline: flag(&lookup, "CECHO_NEWLINE", false)?,
+            output: output(&lookup)?,
+            mode: mode(&lookup)?,
+            passthrough: passthrough(&lookup)?,
+            strict: flag(&lookup, "CECHO_STRICT", true)?,
+        })
+    }
+}
+
+fn output<F>(lookup: &F) -> Result<Output, String>
+    where F: Fn(&str) -> Option<String>
+{
+    match lookup("CECHO_OUTPUT") {
+        None => Ok(Output::Ansi),
+        Some(output) => match output.to_lowercase().as_str() {
+            "ansi" => Ok(Output::Ansi),
+            "html" => Ok(Output::Html),
+            "svg" => Ok(Output::Svg),
+            "json" => Ok(Output::Json),
+            "pango" => Ok(Output::Pango),
+            "i3bar" => Ok(Output::I3bar),
+            "polybar" => Ok(Output::Polybar),
+            "bash" => Ok(Output::Prompt(Shell::Bash)),
+            "zsh" => Ok(Output::Prompt(Shell::Zsh)),
+            "zsh-native" => Ok(Output::Prompt(Shell::ZshNative)),
+            "fish" => Ok(Output::Prompt(Shell::Fish)),
+            "tmux" => Ok(Output::Tmux),
+            "irc" => Ok(Output::Irc),
+            "discord" => Ok(Output::Discord),
+            "debug" => Ok(Output::Debug),
+            _ => Err(format!(
+                "Don't know how to interpret CECHO_OUTPUT='{}', expected ansi, html, svg, json, pango, i3bar, polybar, bash, zsh, zsh-native, fish, tmux, irc, discord or debug",
+                output
+            )),
+        },
+    }
+}
+
+fn mode<F>(lookup: &F) -> Result<Mode, String>
+    where F: Fn(&str) -> Option<String>
+{
+    match lookup("CECHO_MODE") {
+        None => Ok(Mode::Format),
+        Some(mode) => match mode.to_lowercase().as_str() {
+            "format" => Ok(Mode::Format),
+            "strip" => Ok(Mode::Strip),
+            "reverse" => Ok(Mode::Reverse),
+            "check" => Ok(Mode::Check),
+            _ => Err(format!("Don't know how to interpret CECHO_MODE='{}', expected format, strip, reverse or check", mode)),
+        },
+    }
+}
+
+/// `auto` finds the multiplexer from the variables that tmux and screen set for their shells.
+fn passthrough<F>(lookup: &F) -> Result<Passthrough, String>
+    where F: Fn(&str) -> Option<String>
+{
+    match lookup("CECHO_PASSTHROUGH") {
+        None => Ok(Passthrough::Off),
+        Some(passthrough) => match passthrough.to_lowercase().as_str() {
+            "" | "off" | "0" => Ok(Passthrough::Off),
+            "tmux" => Ok(Passthrough::Tmux),
+            "screen" => Ok(Passthrough::Screen),
+            "auto" => Ok(if lookup("TMUX").is_some_and(|it| !it.is_empty()) {
+                Passthrough::Tmux
+            } else if lookup("STY").is_some_and(|it| !it.is_empty()) {
+                Passthrough::Screen
+            } else {
+                Passthrough::Off
+            }),
+            _ => Err(format!("Don't know how to interpret CECHO_PASSTHROUGH='{}', expected auto, tmux, screen or off", passthrough)),
+        },
+    }
+}
+
+/// An empty variable is the same as an unset one, so that `NAME=` doesn't turn off a flag that is on by default.
+fn flag<F>(lookup: &F, name: &str, default: bool) -> Result<bool, String>
+    where F: Fn(&str) -> Option<String>
+{
+    match lookup(name) {
+        None => Ok(default),
+        Some(value) => match value.to_lowercase().as_str() {
+            "" => Ok(default),
+            "0" | "false" | "no" | "off" => Ok(false),
+            "1" | "true" | "yes" | "on" => Ok(true),
+            _ => Err(format!("Don't know how to interpret {}='{}', expected 1 or 0", name, value)),
+        },
+    }
+}
+
+/// Follows the NO_COLOR (https://no-color.org) and CLICOLOR (https://bixense.com/clicolors) conventions.
+fn color_policy<F>(lookup: &F) -> Result<ColorPolicy, String>
+    where F: Fn(&str) -> Option<String>
+{
+    if let Some(policy) = lookup("CECHO_COLOR") {
+        return match policy.to_lowercase().as_str() {
+            "auto" => Ok(ColorPolicy::Auto),
+            "always" => Ok(ColorPolicy::Always),
+            "never" => Ok(ColorPolicy::Never),
+            _ => Err(format!("Don't know how to interpret CECHO_COLOR='{}', expected auto, always or never", policy)),
+        };
+    }
+
+    Ok(if lookup("CLICOLOR_FORCE").is_some_and(|it| !it.is_empty() && it != "0") {
+        ColorPolicy::Always
+    } else if lookup("NO_COLOR").is_some_and(|it| !it.is_empty()) || lookup("CLICOLOR").as_deref() == Some("0") {
+        ColorPolicy::Never
+    } else {
+        ColorPolicy::Auto
+    })
+}
+
+fn detect_depth<F>(lookup: &F) -> Result<ColorDepth, String>
+    where F: Fn(&str) -> Option<String>
+{
+    if let Some(depth) = lookup("CECHO_COLOR_DEPTH") {
+        return match depth.to_lowercase().as_str() {
+            "truecolor" | "24bit" | "24" => Ok(ColorDepth::TrueColor),
+            "256" | "8bit" | "8" => Ok(ColorDepth::Palette256),
+            "16" | "4bit" | "4" => Ok(ColorDepth::Ansi16),
+            "mono" | "monochrome" | "1" => Ok(ColorDepth::Monochrome),
+            _ => Err(format!("Don't know how to interpret CECHO_COLOR_DEPTH='{}', expected truecolor, 256, 16 or mono", depth)),
+        };
+    }
+
+    if let Some("truecolor" | "24bit") = lookup("COLORTERM").as_deref() {
+        return Ok(ColorDepth::TrueColor);
+    }
+
+    Ok(match lookup("TERM") {
+        Some(term) if term == "dumb" => ColorDepth::Monochrome,
+        Some(term) if term.ends_with("-direct") => ColorDepth::TrueColor,
+        Some(term) if term.contains("256color") => ColorDepth::Palette256,
+        _ => ColorDepth::Ansi16,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{ColorDepth, Config, Mode, Output, Passthrough};
+
+    fn config(variables: &[(&str, &str)], is_terminal: bool) -> Result<Config, String> {
+        Config::from_lookup(
+            |name| variables.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string()),
+            is_terminal,
+        )
+    }
+
+    fn depth(variables: &[(&str, &str)]) -> Result<ColorDepth, String> {
+        config(variables, true).map(|it| it.depth)
+    }
+
+    fn colored(variables: &[(&str, &str)], is_terminal: bool) -> Result<bool, String> {
+        config(variables, is_terminal).map(|it| it.colored)
+    }
+
+    fn preserve(variables: &[(&str, &str)]) -> Result<bool, String> {
+        config(variables, true).map(|it| it.preserve)
+    }
+
+    fn newline(variables: &[(&str, &str)]) -> Result<bool, String> {
+        config(variables, true).map(|it| it.newline)
+    }
+
+    #[test]
+    fn detect_truecolor_from_colorterm() {
+        assert_eq!(depth(&[("COLORTERM", "truecolor"), ("TERM", "xterm-256color")]), Ok(ColorDepth::TrueColor));
+        assert_eq!(depth(&[("COLORTERM", "24bit")]), Ok(ColorDepth::TrueColor));
+    }
+
+    #[test]
+    fn detect_the_depth_from_term() {
+        assert_eq!(depth(&[("TERM", "xterm-256color")]), Ok(ColorDepth::Palette256));
+        assert_eq!(depth(&[("TERM", "xterm-direct")]), Ok(ColorDepth::TrueColor));
+        assert_eq!(depth(&[("TERM", "linux")]), Ok(ColorDepth::Ansi16));
+        assert_eq!(depth(&[("TERM", "dumb")]), Ok(ColorDepth::Monochrome));
+        assert_eq!(depth(&[]), Ok(ColorDepth::Ansi16));
+    }
+
+    #[test]
+    fn the_depth_can_be_overridden() {
+        assert_eq!(depth(&[("CECHO_COLOR_DEPTH", "256"), ("COLORTERM", "truecolor")]), Ok(ColorDepth::Palette256));
+        assert_eq!(depth(&[("CECHO_COLOR_DEPTH", "mono")]), Ok(ColorDepth::Monochrome));
+        assert_eq!(
+            depth(&[("CECHO_COLOR_DEPTH", "lots")]),
+            Err("Don't know how to interpret CECHO_COLOR_DEPTH='lots', expected truecolor, 256, 16 or mono".to_string())
+        );
+    }
+
+    #[test]
+    fn color_only_terminals_by_default() {
+        assert_eq!(colored(&[], true), Ok(true));
+        assert_eq!(colored(&[], false), Ok(false));
+    }
+
+    #[test]
+    fn honor_no_color() {
+        assert_eq!(colored(&[("NO_COLOR", "1")], true), Ok(false));
+        assert_eq!(colored(&[("NO_COLOR", "")], true), Ok(true));
+    }
+
+    #[test]
+    fn honor_clicolor() {
+        assert_eq!(colored(&[("CLICOLOR", "0")], true), Ok(false));
+        assert_eq!(colored(&[("CLICOLOR", "1")], false), Ok(false));
+        assert_eq!(colored(&[("CLICOLOR_FORCE", "1")], false), Ok(true));
+        assert_eq!(colored(&[("CLICOLOR_FORCE", "0")], false), Ok(false));
+        assert_eq!(colored(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")], false), Ok(true));
+    }
+
+    #[test]
+    fn the_color_policy_can_be_overridden() {
+        assert_eq!(colored(&[("CECHO_COLOR", "always"), ("NO_COLOR", "1")], false), Ok(true));
+        assert_eq!(colored(&[("CECHO_COLOR", "never"), ("CLICOLOR_FORCE", "1")], true), Ok(false));
+        assert_eq!(colored(&[("CECHO_COLOR", "auto"), ("NO_COLOR", "1")], true), Ok(true));
+        assert_eq!(
+            colored(&[("CECHO_COLOR", "sometimes")], true),
+            Err("Don't know how to interpret CECHO_COLOR='sometimes', expected auto, always or never".to_string())
+        );
+    }
+
+    #[test]
+    fn preserve_the_styles_on_demand() {
+        assert_eq!(preserve(&[]), Ok(false));
+        assert_eq!(preserve(&[("CECHO_PRESERVE", "1")]), Ok(true));
+        assert_eq!(preserve(&[("CECHO_PRESERVE", "off")]), Ok(false));
+        assert_eq!(
+            preserve(&[("CECHO_PRESERVE", "maybe")]),
+            Err("Don't know how to interpret CECHO_PRESERVE='maybe', expected 1 or 0".to_string())
+        );
+    }
+
+    #[test]
+    fn end_with_a_newline_on_demand() {
+        assert_eq!(newline(&[]), Ok(false));
+        assert_eq!(newline(&[("CECHO_NEWLINE", "yes")]), Ok(true));
+        assert_eq!(newline(&[("CECHO_NEWLINE", "0")]), Ok(false));
+    }
+
+    #[test]
+    fn be_strict_unless_told_otherwise() {
+        let strict = |variables: &[(&str, &str)]| config(variables, true).map(|it| it.strict);
+
+        assert_eq!(strict(&[]), Ok(true));
+        assert_eq!(strict(&[("CECHO_STRICT", "0")]), Ok(false));
+        assert_eq!(strict(&[("CECHO_STRICT", "")]), Ok(true));
+    }
+
+    #[test]
+    fn select_the_output_and_the_mode() {
+        assert_eq!(config(&[], true).map(|it| (it.output, it.mode)), Ok((Output::Ansi, Mode::Format)));
+        assert_eq!(config(&[("CECHO_MODE", "strip")], true).map(|it| it.mode), Ok(Mode::Strip));
+        assert_eq!(config(&[("CECHO_OUTPUT", "i3bar")], true).map(|it| it.output), Ok(Output::I3bar));
+        assert_eq!(
+            config(&[("CECHO_OUTPUT", "pdf")], true),
+            Err("Don't know how to interpret CECHO_OUTPUT='pdf', expected ansi, html, svg, json, pango, i3bar, polybar, bash, zsh, zsh-native, fish, tmux, irc, discord or debug".to_string())
+        );
+        assert_eq!(
+            config(&[("CECHO_MODE", "dance")], true),
+            Err("Don't know how to interpret CECHO_MODE='dance', expected format, strip, reverse or check".to_string())
+        );
+    }
+
+    #[test]
+    fn find_the_multiplexer_for_the_passthrough() {
+        let passthrough = |variables: &[(&str, &str)]| config(variables, true).map(|it| it.passthrough);
+
+        assert_eq!(passthrough(&[("TMUX", "/tmp/tmux-1000/default,1,0")]), Ok(Passthrough::Off));
+        assert_eq!(passthrough(&[("CECHO_PASSTHROUGH", "screen")]), Ok(Passthrough::Screen));
+        assert_eq!(passthrough(&[("CECHO_PASSTHROUGH", "auto"), ("TMUX", "/tmp/tmux-1000/default,1,0")]), Ok(Passthrough::Tmux));
+        assert_eq!(passthrough(&[("CECHO_PASSTHROUGH", "auto"), ("STY", "1234.pts-0.host")]), Ok(Passthrough::Screen));
+        assert_eq!(passthrough(&[("CECHO_PASSTHROUGH", "auto")]), Ok(Passthrough::Off));
+        assert_eq!(
+            passthrough(&[("CECHO_PASSTHROUGH", "zellij")]),
+            Err("Don't know how to interpret CECHO_PASSTHROUGH='zellij', expected auto, tmux, screen or off".to_string())
+        );
+    }
+}
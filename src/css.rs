@@ -0,0 +1,306 @@
+use crate::color_space::{hsl_to_srgb, hwb_to_srgb, lab_to_srgb, lch_to_srgb, oklab_to_srgb, oklch_to_srgb, srgb_to_bytes};
+use crate::model::Color;
+
+/// The CSS Color Module Level 4 named colors.
+///
+/// The 8 ANSI names (black, red, green, yellow, blue, magenta, cyan, white) are left out on purpose:
+/// they keep their terminal meaning, lower case for the regular variant and upper case for the bright one.
+const NAMED_COLORS: [(&str, u32); 140] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("blanchedalmond", 0xffebcd),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellowgreen", 0x9acd32),
+];
+
+/// Looks up a CSS named color, ignoring the case.
+pub fn named_color(name: &str) -> Option<Color> {
+    let lower = name.to_lowercase();
+    NAMED_COLORS
+        .iter()
+        .find(|(it, _)| *it == lower)
+        .map(|(_, value)| Color::u32_rgb(*value))
+}
+
+enum Value {
+    Number(f64),
+    Percentage(f64),
+    Angle(f64),
+}
+
+/// Interprets the CSS color functions: `rgb()`, `hsl()`, `hwb()`, `lab()`, `lch()`, `oklab()` and `oklch()`.
+///
+/// The arguments may be separated with commas, like in `rgb(84, 55, 15)`, or with spaces, like in `hsl(120deg 50% 50%)`.
+///
+/// Returns the color and its opacity.
+pub fn color_function(name: &str, arguments: &str) -> Result<(Color, f64), String> {
+    let (values, alpha) = split_arguments(arguments)?;
+    let opacity = alpha.map(|it| fraction("opacity", it)).transpose()?.unwrap_or(1.0);
+
+    let [a, b, c] = match values.as_slice() {
+        [a, b, c] => [parse_value(a)?, parse_value(b)?, parse_value(c)?],
+        _ => return Err(format!("The function '{}' takes 3 components but got {}", name, values.len())),
+    };
+
+    let srgb = match name {
+        "rgb" | "rgba" => {
+            let color = Color::rgb(
+                rgb_component("red", a)?,
+                rgb_component("green", b)?,
+                rgb_component("blue", c)?,
+            );
+            return Ok((color, opacity));
+        }
+        "hsl" | "hsla" => hsl_to_srgb(hue(a)?, percentage("saturation", b)?, percentage("lightness", c)?),
+        "hwb" => hwb_to_srgb(hue(a)?, percentage("whiteness", b)?, percentage("blackness", c)?),
+        "lab" => lab_to_srgb(lab_lightness(a)?, scaled("a", b, 125.0)?, scaled("b", c, 125.0)?),
+        "lch" => lch_to_srgb(lab_lightness(a)?, chroma(b, 150.0)?, hue(c)?),
+        "oklab" => oklab_to_srgb(oklab_lightness(a)?, scaled("a", b, 0.4)?, scaled("b", c, 0.4)?),
+        "oklch" => oklch_to_srgb(oklab_lightness(a)?, chroma(b, 0.4)?, hue(c)?),
+        _ => return Err(format!("Don't know the color function '{}'", name)),
+    };
+
+    let [red, green, blue] = srgb_to_bytes(srgb);
+    Ok((Color::rgb(red, green, blue), opacity))
+}
+
+/// A fraction between 0 and 1, given as a number between 0 and 1 or as a percentage.
+pub fn fraction(name: &str, value: &str) -> Result<f64, String> {
+    match parse_value(value.trim())? {
+        Value::Number(it) if (0.0..=1.0).contains(&it) => Ok(it),
+        Value::Number(it) => Err(format!("The {} {} is out of range, it must be between 0 and 1", name, it)),
+        Value::Percentage(it) if (0.0..=100.0).contains(&it) => Ok(it / 100.0),
+        Value::Percentage(it) => Err(format!("The {} {}% is out of range, it must be between 0% and 100%", name, it)),
+        Value::Angle(_) => Err(format!("The {} can't be an angle", name)),
+    }
+}
+
+fn split_arguments(arguments: &str) -> Result<(Vec<&str>, Option<&str>), String> {
+    if arguments.contains(',') {
+        let mut values: Vec<&str> = arguments.split(',').map(|it| it.trim()).collect();
+        let alpha = if values.len() == 4 { values.pop() } else { None };
+        Ok((values, alpha))
+    } else {
+        let (values, alpha) = match arguments.split_once('/') {
+            Some((values, alpha)) => (values, Some(alpha.trim())),
+            None => (arguments, None),
+        };
+        Ok((values.split_whitespace().collect(), alpha))
+    }
+}
+
+fn parse_value(value: &str) -> Result<Value, String> {
+    let number = |it: &str| it.trim().parse::<f64>()
+        .map_err(|_| format!("Don't know how to interpret the value '{}' in a color", value));
+
+    if let Some(it) = value.strip_suffix('%') {
+        Ok(Value::Percentage(number(it)?))
+    } else if let Some(it) = value.strip_suffix("deg") {
+        Ok(Value::Angle(number(it)?))
+    } else if let Some(it) = value.strip_suffix("grad") {
+        Ok(Value::Angle(number(it)? * 0.9))
+    } else if let Some(it) = value.strip_suffix("rad") {
+        Ok(Value::Angle(number(it)?.to_degrees()))
+    } else if let Some(it) = value.strip_suffix("turn") {
+        Ok(Value::Angle(number(it)? * 360.0))
+    } else {
+        Ok(Value::Number(number(value)?))
+    }
+}
+
+fn rgb_component(name: &str, value: Value) -> Result<u8, String> {
+    match value {
+        Value::Number(it) if (0.0..=255.0).contains(&it) => Ok(it.round() as u8),
+        Value::Number(it) => Err(format!("The {} component {} is out of range, it must be between 0 and 255", name, it)),
+        Value::Percentage(it) if (0.0..=100.0).contains(&it) => Ok((it / 100.0 * 255.0).round() as u8),
+        Value::Percentage(it) => Err(format!("The {} component {}% is out of range, it must be between 0% and 100%", name, it)),
+        Value::Angle(_) => Err(format!("The {} component can't be an angle", name)),
+    }
+}
+
+fn hue(value: Value) -> Result<f64, String> {
+    match value {
+        Value::Number(it) | Value::Angle(it) => Ok(it),
+        Value::Percentage(_) => Err("The hue can't be a percentage".to_string()),
+    }
+}
+
+/// A percentage between 0 and 1. Plain numbers are also read as percentages.
+fn percentage(name: &str, value: Value) -> Result<f64, String> {
+    match value {
+        Value::Number(it) | Value::Percentage(it) if (0.0..=100.0).contains(&it) => Ok(it / 100.0),
+        Value::Number(it) | Value::Percentage(it) => Err(format!("The {} {}% is out of range, it must be between 0% and 100%", name, it)),
+        Value::Angle(_) => Err(format!("The {} can't be an angle", name)),
+    }
+}
+
+fn lab_lightness(value: Value) -> Result<f64, String> {
+    match value {
+        Value::Number(it) | Value::Percentage(it) if (0.0..=100.0).contains(&it) => Ok(it),
+        Value::Number(it) | Value::Percentage(it) => Err(format!("The lightness {} is out of range, it must be between 0 and 100", it)),
+        Value::Angle(_) => Err("The lightness can't be an angle".to_string()),
+    }
+}
+
+fn oklab_lightness(value: Value) -> Result<f64, String> {
+    match value {
+        Value::Number(it) if (0.0..=1.0).contains(&it) => Ok(it),
+        Value::Number(it) => Err(format!("The lightness {} is out of range, it must be between 0 and 1", it)),
+        Value::Percentage(it) if (0.0..=100.0).contains(&it) => Ok(it / 100.0),
+        Value::Percentage(it) => Err(format!("The lightness {}% is out of range, it must be between 0% and 100%", it)),
+        Value::Angle(_) => Err("The lightness can't be an angle".to_string()),
+    }
+}
+
+/// An axis where 100% means `full_scale`.
+fn scaled(name: &str, value: Value, full_scale: f64) -> Result<f64, String> {
+    match value {
+        Value::Number(it) => Ok(it),
+        Value::Percentage(it) => Ok(it / 100.0 * full_scale),
+        Value::Angle(_) => Err(format!("The {} axis can't be an angle", name)),
+    }
+}
+
+fn chroma(value: Value, full_scale: f64) -> Result<f64, String> {
+    let chroma = scaled("chroma", value, full_scale)?;
+    if chroma < 0.0 {
+        Err(format!("The chroma {} is out of range, it can't be negative", chroma))
+    } else {
+        Ok(chroma)
+    }
+}
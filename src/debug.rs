@@ -0,0 +1,160 @@
+use crate::config::Config;
+use crate::html::css_color;
+use crate::json::style_name;
+use crate::model::{Appearance, Color, Part};
+use crate::model::Color::{Byte, RGB};
+use crate::span::Spans;
+use crate::writer::sgr_transition;
+
+const OPEN: char = '⟨';
+const CLOSE: char = '⟩';
+
+/// Outputs what cecho understood of the format and what it sends to the terminal, for the bug reports.
+///
+/// The escape sequences are made visible and followed by what the terminal displays after them:
+///
+/// ```text
+/// ⟨ESC[1;31m bold red⟩error⟨ESC[0m reset⟩
+/// ```
+pub fn render_debug(parts: &[Part], spans: &Spans, config: &Config) -> String {
+    let mut lines = vec!("Parts:".to_string());
+    lines.extend(parts.iter().map(|it| format!("  {:?}", it)));
+    lines.push(format!("Depth: {:?}", config.depth));
+    lines.push(format!("Output: {}", annotate(spans)));
+
+    lines.join("\n")
+}
+
+/// The same output as the terminal's, with the escape sequences and the control characters made visible.
+fn annotate(spans: &Spans) -> String {
+    let mut result = String::new();
+    let mut displayed = Appearance::plain();
+
+    for span in spans.spans.iter().filter(|it| !it.text.is_empty()) {
+        result.push_str(&sequence(&displayed, &span.appearance));
+        result.push_str(&visible(&span.text));
+        displayed = span.appearance.clone();
+    }
+
+    result.push_str(&sequence(&displayed, &spans.end));
+
+    result
+}
+
+fn sequence(from: &Appearance, to: &Appearance) -> String {
+    let sequence = sgr_transition(from, to);
+
+    if sequence.is_empty() {
+        sequence
+    } else {
+        format!("{}ESC{} {}{}", OPEN, &sequence[1..], describe(to), CLOSE)
+    }
+}
+
+fn visible(text: &str) -> String {
+    let mut result = String::new();
+
+    for c in text.chars() {
+        let name = match c {
+            '\x07' => "BEL".to_string(),
+            '\x08' => "BS".to_string(),
+            '\t' => "TAB".to_string(),
+            '\n' => "LF".to_string(),
+            '\x0b' => "VT".to_string(),
+            '\x0c' => "FF".to_string(),
+            '\r' => "CR".to_string(),
+            '\x1b' => "ESC".to_string(),
+            _ if c.is_control() => format!("0x{:02x}", c as u32),
+            _ => {
+                result.push(c);
+                continue;
+            }
+        };
+
+        result.push_str(&format!("{}{}{}", OPEN, name, CLOSE));
+    }
+
+    result
+}
+
+/// What the terminal displays, like `bold red on blue`.
+fn describe(appearance: &Appearance) -> String {
+    if appearance.is_plain() {
+        return "reset".to_string();
+    }
+
+    let colors = &appearance.colors;
+    let mut words: Vec<String> = appearance.styles.iter().map(style_name).collect();
+
+    if let Some(color) = colors.foreground {
+        words.push(color_name(color));
+    }
+    if let Some(color) = colors.background {
+        words.push(format!("on {}", color_name(color)));
+    }
+    if let Some(color) = colors.underline {
+        words.push(format!("underline {}", color_name(color)));
+    }
+
+    words.join(" ")
+}
+
+fn color_name(color: Color) -> String {
+    const NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+    match color {
+        Byte(b) if b < 8 => NAMES[b as usize].to_string(),
+        Byte(b) if b < 16 => format!("bright {}", NAMES[b as usize - 8]),
+        Byte(b) => format!("color {}", b),
+        RGB { .. } => css_color(color),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{ColorDepth, Config};
+    use crate::debug::{annotate, render_debug};
+    use crate::model::{Appearance, Color, Colors, Part};
+    use crate::model::Style::{Italic, Strong};
+    use crate::span::spans_of;
+
+    #[test]
+    fn annotate_the_escape_sequences() {
+        let bold_red = Appearance::plain().with(&Colors::new_fg(Color::red()), &[Strong]);
+        let red = Appearance::plain().with(&Colors::new_fg(Color::red()), &[]);
+
+        assert_eq!(
+            annotate(&spans_of(vec!(("error", bold_red), (":", red), (" disk", Appearance::plain())))),
+            "⟨ESC[1;31m bold red⟩error⟨ESC[22m red⟩:⟨ESC[0m reset⟩ disk"
+        );
+    }
+
+    #[test]
+    fn name_the_colors_and_show_the_control_characters() {
+        let styled = Appearance::plain().with(
+            &Colors { foreground: Some(Color::bright_blue()), background: Some(Color::palette(208)), underline: Some(Color::rgb(1, 2, 3)) },
+            &[Italic],
+        );
+
+        assert_eq!(
+            annotate(&spans_of(vec!(("a\tb\x1b]0;title\x07", styled)))),
+            "⟨ESC[3;94;48;5;208;58;2;1;2;3m italic bright blue on color 208 underline #010203⟩a⟨TAB⟩b⟨ESC⟩]0;title⟨BEL⟩⟨ESC[0m reset⟩"
+        );
+    }
+
+    #[test]
+    fn dump_the_parts_and_the_depth() {
+        let config = Config { depth: ColorDepth::Palette256, ..Config::default() };
+
+        assert_eq!(
+            render_debug(&[Part::literal("x="), Part::positional()], &spans_of(vec!(("x=1", Appearance::plain()))), &config),
+            [
+                "Parts:",
+                "  Literal(\"x=\")",
+                "  Specification { text: Positional, color: Colors { foreground: None, background: None, underline: None }, styles: [], preserve: false }",
+                "Depth: Palette256",
+                "Output: x=1",
+            ].join("\n")
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use crate::downsample::nearest_in;
+use crate::model::{Appearance, Color, Colors, Style};
+use crate::span::Spans;
+use crate::writer::on_codes;
+
+/// The colors of Discord's `ansi` code blocks for the codes 30 to 37, a solarized palette rather than the usual one.
+const DISCORD_FOREGROUNDS: [[u8; 3]; 8] = [
+    [0x4f, 0x54, 0x5c], [0xdc, 0x32, 0x2f], [0x85, 0x99, 0x00], [0xb5, 0x89, 0x00],
+    [0x26, 0x8b, 0xd2], [0xd3, 0x36, 0x82], [0x2a, 0xa1, 0x98], [0xff, 0xff, 0xff],
+];
+
+/// The colors for the codes 40 to 47.
+const DISCORD_BACKGROUNDS: [[u8; 3]; 8] = [
+    [0x00, 0x2b, 0x36], [0xcb, 0x4b, 0x16], [0x58, 0x6e, 0x75], [0x65, 0x7b, 0x83],
+    [0x83, 0x94, 0x96], [0x6c, 0x71, 0xc4], [0x93, 0xa1, 0xa1], [0xfd, 0xf6, 0xe3],
+];
+
+/// Outputs the text in a Discord code block with the `ansi` syntax highlighting.
+///
+/// Discord only knows the 8 colors of each palette, bold and underline, and the reset code:
+/// the colors are mapped to the nearest ones and every change starts with a reset.
+pub fn render_discord(spans: &Spans) -> String {
+    let mut result = String::from("```ansi\n");
+    let mut displayed = Appearance::plain();
+
+    for span in spans.spans.iter().filter(|it| !it.text.is_empty()) {
+        let next = discord_appearance(&span.appearance);
+        result.push_str(&transition(&displayed, &next));
+        // a zero-width space keeps the backticks from closing the code block
+        result.push_str(&span.text.replace("``", "`\u{200b}`"));
+        displayed = next;
+    }
+
+    result.push_str(&transition(&displayed, &Appearance::plain()));
+    result.push_str("\n```");
+
+    result
+}
+
+fn transition(from: &Appearance, to: &Appearance) -> String {
+    if from == to {
+        String::new()
+    } else if to.is_plain() {
+        "\x1b[0m".to_string()
+    } else {
+        format!("\x1b[0;{}m", on_codes(to).join(";"))
+    }
+}
+
+/// What Discord can display of the appearance.
+fn discord_appearance(appearance: &Appearance) -> Appearance {
+    let nearest = |color: Color, palette: &[[u8; 3]]| Color::palette(nearest_in(&color, palette) as u8);
+
+    let mut styles = Vec::new();
+    if appearance.styles.contains(&Style::Strong) {
+        styles.push(Style::Strong);
+    }
+    if appearance.styles.iter().any(Style::is_underline) {
+        styles.push(Style::Underline);
+    }
+
+    Appearance {
+        colors: Colors {
+            foreground: appearance.colors.foreground.map(|it| nearest(it, &DISCORD_FOREGROUNDS)),
+            background: appearance.colors.background.map(|it| nearest(it, &DISCORD_BACKGROUNDS)),
+            underline: None,
+        },
+        styles,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::discord::render_discord;
+    use crate::model::{Appearance, Color, Colors};
+    use crate::model::Style::{CurlyUnderline, Italic, Strong};
+    use crate::span::spans_of;
+
+    fn discord(spans: Vec<(&str, Appearance)>) -> String {
+        render_discord(&spans_of(spans))
+    }
+
+    #[test]
+    fn output_a_code_block_with_the_colors_and_styles() {
+        let failed = Appearance::plain().with(&Colors::new_fg(Color::red()), &[Strong, Italic]);
+
+        assert_eq!(
+            discord(vec!(("FAILED", failed), (" build 42", Appearance::plain()))),
+            "```ansi\n\x1b[0;1;31mFAILED\x1b[0m build 42\n```"
+        );
+    }
+
+    #[test]
+    fn map_the_colors_to_discord_palettes() {
+        let styled = Appearance::plain().with(&Colors::new(Color::rgb(40, 140, 210), Color::rgb(200, 80, 20)), &[CurlyUnderline]);
+
+        assert_eq!(discord(vec!(("ok", styled))), "```ansi\n\x1b[0;4;34;41mok\x1b[0m\n```");
+    }
+
+    #[test]
+    fn keep_the_backticks_from_closing_the_code_block() {
+        assert_eq!(discord(vec!(("```", Appearance::plain()))), "```ansi\n`\u{200b}``\n```");
+    }
+}
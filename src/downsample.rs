@@ -0,0 +1,87 @@
+use lazy_static::lazy_static;
+
+use crate::color_space::{bytes_to_srgb, ciede2000, srgb_to_lab};
+use crate::config::ColorDepth;
+use crate::model::Color;
+use crate::model::Color::{Byte, RGB};
+
+lazy_static! {
+    static ref PALETTE_LAB: Vec<[f64; 3]> = (0..=255u8)
+        .map(|it| lab(&Byte(it)))
+        .collect();
+}
+
+/// Maps the color to the perceptually nearest color that the terminal can display.
+///
+/// Returns `None` when the terminal can't display colors.
+pub fn downsample(color: Color, depth: ColorDepth) -> Option<Color> {
+    match (depth, color) {
+        (ColorDepth::Monochrome, _) => None,
+        (ColorDepth::TrueColor, _) => Some(color),
+        (ColorDepth::Palette256, Byte(_)) => Some(color),
+        // the first 16 colors depend on the terminal's theme, only the fixed colors are reliable
+        (ColorDepth::Palette256, RGB { .. }) => Some(nearest(&color, 16..=255)),
+        (ColorDepth::Ansi16, Byte(b)) if b < 16 => Some(color),
+        (ColorDepth::Ansi16, _) => Some(nearest(&color, 0..=15)),
+    }
+}
+
+fn nearest(color: &Color, candidates: std::ops::RangeInclusive<u8>) -> Color {
+    let target = lab(color);
+
+    candidates
+        .map(|it| (it, ciede2000(target, PALETTE_LAB[it as usize])))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(it, _)| Byte(it))
+        .unwrap_or(*color)
+}
+
+/// The index of the perceptually nearest color in another palette, like the ones of the chat applications.
+pub fn nearest_in(color: &Color, palette: &[[u8; 3]]) -> usize {
+    let target = lab(color);
+
+    palette.iter()
+        .map(|it| ciede2000(target, srgb_to_lab(bytes_to_srgb(*it))))
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(index, _)| index)
+        .unwrap_or(0)
+}
+
+fn lab(color: &Color) -> [f64; 3] {
+    srgb_to_lab(bytes_to_srgb(color.to_rgb()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ColorDepth::{Ansi16, Monochrome, Palette256, TrueColor};
+    use crate::downsample::downsample;
+    use crate::model::Color;
+
+    #[test]
+    fn keep_the_colors_in_truecolor() {
+        assert_eq!(downsample(Color::rgb(84, 55, 15), TrueColor), Some(Color::rgb(84, 55, 15)));
+    }
+
+    #[test]
+    fn map_rgb_to_the_256_colors_palette() {
+        assert_eq!(downsample(Color::rgb(255, 135, 0), Palette256), Some(Color::palette(208)));
+        assert_eq!(downsample(Color::rgb(255, 136, 2), Palette256), Some(Color::palette(208)));
+        assert_eq!(downsample(Color::rgb(128, 128, 128), Palette256), Some(Color::gray(12)));
+        assert_eq!(downsample(Color::red(), Palette256), Some(Color::red()));
+    }
+
+    #[test]
+    fn map_to_the_16_named_colors() {
+        assert_eq!(downsample(Color::rgb(255, 0, 0), Ansi16), Some(Color::bright_red()));
+        assert_eq!(downsample(Color::rgb(200, 10, 10), Ansi16), Some(Color::red()));
+        assert_eq!(downsample(Color::gray(0), Ansi16), Some(Color::black()));
+        assert_eq!(downsample(Color::cyan(), Ansi16), Some(Color::cyan()));
+    }
+
+    #[test]
+    fn drop_the_colors_in_monochrome() {
+        assert_eq!(downsample(Color::red(), Monochrome), None);
+        assert_eq!(downsample(Color::rgb(1, 2, 3), Monochrome), None);
+    }
+}
@@ -2,5 +2,3 @@
 macro_rules! vecs {
     ($($x:expr),*) => (vec![$($x.to_string()),*]);
 }
-
-pub(crate) use vecs;
@@ -0,0 +1,196 @@
+use crate::model::{Appearance, Color, Style};
+use crate::span::Spans;
+
+/// Outputs the text as HTML, with a `<span>` and inline CSS for each piece of text that has colors or styles.
+///
+/// The whitespace is kept as it is, so the result belongs in a `<pre>` or an element with `white-space: pre`.
+pub fn render_html(spans: &Spans) -> String {
+    let mut result = String::new();
+    let mut pieces = spans.spans.iter().filter(|it| !it.text.is_empty()).peekable();
+
+    while let Some(first) = pieces.next() {
+        // the adjacent pieces that look the same share their span
+        let mut text = first.text.clone();
+        while let Some(next) = pieces.next_if(|it| it.appearance == first.appearance) {
+            text.push_str(&next.text);
+        }
+
+        let declarations = declarations(&first.appearance);
+
+        if declarations.is_empty() {
+            result.push_str(&escape(&text));
+        } else {
+            result.push_str(&format!("<span style=\"{}\">{}</span>", declarations.join(";"), escape(&text)));
+        }
+    }
+
+    result
+}
+
+/// The CSS declarations that display the colors and styles.
+///
+/// Blinking and the alternative fonts have no equivalent and are left out.
+fn declarations(appearance: &Appearance) -> Vec<String> {
+    let styles = &appearance.styles;
+    let has = |style: Style| styles.contains(&style);
+    let mut declarations = Vec::new();
+
+    let (foreground, background) = if has(Style::Reversed) {
+        // the system colors of the page stand for the terminal's default colors
+        (
+            Some(appearance.colors.background.map(css_color).unwrap_or("Canvas".to_string())),
+            Some(appearance.colors.foreground.map(css_color).unwrap_or("CanvasText".to_string())),
+        )
+    } else {
+        (appearance.colors.foreground.map(css_color), appearance.colors.background.map(css_color))
+    };
+
+    if let Some(color) = foreground {
+        declarations.push(format!("color:{}", color));
+    }
+    if let Some(color) = background {
+        declarations.push(format!("background-color:{}", color));
+    }
+
+    if has(Style::Strong) {
+        declarations.push("font-weight:bold".to_string());
+    }
+    if has(Style::Dim) {
+        declarations.push("opacity:0.5".to_string());
+    }
+    if has(Style::Italic) {
+        declarations.push("font-style:italic".to_string());
+    }
+    if has(Style::Hidden) {
+        declarations.push("visibility:hidden".to_string());
+    }
+    if has(Style::Superscript) {
+        declarations.push("vertical-align:super;font-size:smaller".to_string());
+    }
+    if has(Style::Subscript) {
+        declarations.push("vertical-align:sub;font-size:smaller".to_string());
+    }
+    // a single border for both, rounded when encircled
+    if has(Style::Encircled) {
+        declarations.push("border:1px solid;border-radius:1em".to_string());
+    } else if has(Style::Framed) {
+        declarations.push("border:1px solid".to_string());
+    }
+    if has(Style::Fraktur) {
+        declarations.push("font-family:fantasy".to_string());
+    }
+
+    let underline = styles.iter().find_map(|it| match it {
+        Style::Underline => Some("solid"),
+        Style::DoubleUnderline | Style::DoublyUnderlined => Some("double"),
+        Style::CurlyUnderline => Some("wavy"),
+        Style::DottedUnderline => Some("dotted"),
+        Style::DashedUnderline => Some("dashed"),
+        _ => None,
+    });
+
+    let lines: Vec<&str> = [
+        (underline.is_some(), "underline"),
+        (has(Style::Overline), "overline"),
+        (has(Style::CrossedOut), "line-through"),
+    ].iter().filter(|(present, _)| *present).map(|(_, line)| *line).collect();
+
+    if !lines.is_empty() {
+        declarations.push(format!("text-decoration-line:{}", lines.join(" ")));
+    }
+    if let Some(kind) = underline.filter(|it| *it != "solid") {
+        declarations.push(format!("text-decoration-style:{}", kind));
+    }
+    if let Some(color) = appearance.colors.underline {
+        declarations.push(format!("text-decoration-color:{}", css_color(color)));
+    }
+
+    declarations
+}
+
+pub fn css_color(color: Color) -> String {
+    let [red, green, blue] = color.to_rgb();
+    format!("#{:02x}{:02x}{:02x}", red, green, blue)
+}
+
+/// Escapes the text for HTML and XML.
+pub fn escape(text: &str) -> String {
+    text.chars().map(|c| match c {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        '\'' => "&#39;".to_string(),
+        _ => c.to_string(),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::html::render_html;
+    use crate::model::{Appearance, Color, Colors};
+    use crate::model::Style::{CurlyUnderline, CrossedOut, Encircled, Framed, Reversed, Strong};
+    use crate::span::spans_of;
+
+    fn html(spans: Vec<(&str, Appearance)>) -> String {
+        render_html(&spans_of(spans))
+    }
+
+    #[test]
+    fn wrap_the_styled_text_in_spans() {
+        let red_bold = Appearance::plain().with(&Colors::new(Color::red(), Color::rgb(1, 2, 3)), &[Strong]);
+
+        assert_eq!(
+            html(vec!(("error: ", red_bold), ("disk full", Appearance::plain()))),
+            "<span style=\"color:#cd0000;background-color:#010203;font-weight:bold\">error: </span>disk full"
+        );
+    }
+
+    #[test]
+    fn escape_the_text() {
+        assert_eq!(
+            html(vec!(("<a href=\"x\">Tom & Jerry's</a>", Appearance::plain()))),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn merge_the_adjacent_pieces_that_look_the_same() {
+        let bold = Appearance::plain().with(&Colors::none(), &[Strong]);
+
+        assert_eq!(
+            html(vec!(("a", bold.clone()), ("", Appearance::plain()), ("b", bold))),
+            "<span style=\"font-weight:bold\">ab</span>"
+        );
+    }
+
+    #[test]
+    fn combine_the_text_decorations() {
+        let decorated = Appearance::plain().with(&Colors::new_underline(Color::rgb(255, 0, 0)), &[CurlyUnderline, CrossedOut]);
+
+        assert_eq!(
+            html(vec!(("typo", decorated))),
+            "<span style=\"text-decoration-line:underline line-through;text-decoration-style:wavy;text-decoration-color:#ff0000\">typo</span>"
+        );
+    }
+
+    #[test]
+    fn swap_the_colors_when_reversed() {
+        let reversed = Appearance::plain().with(&Colors::new_fg(Color::rgb(0, 0, 255)), &[Reversed]);
+
+        assert_eq!(
+            html(vec!(("x", reversed))),
+            "<span style=\"color:Canvas;background-color:#0000ff\">x</span>"
+        );
+    }
+
+    #[test]
+    fn draw_a_single_border_when_framed_and_encircled() {
+        let both = Appearance::plain().with(&Colors::none(), &[Framed, Encircled]);
+
+        assert_eq!(
+            html(vec!(("x", both))),
+            "<span style=\"border:1px solid;border-radius:1em\">x</span>"
+        );
+    }
+}
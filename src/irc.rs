@@ -0,0 +1,169 @@
+use crate::downsample::nearest_in;
+use crate::model::{Appearance, Color, Style};
+use crate::span::Spans;
+
+const BOLD: char = '\x02';
+const COLOR: char = '\x03';
+const ITALIC: char = '\x1d';
+const UNDERLINE: char = '\x1f';
+const STRIKETHROUGH: char = '\x1e';
+const REVERSE: char = '\x16';
+const RESET: char = '\x0f';
+
+/// The colors that mIRC gives to the 16 color codes, most clients follow it.
+const IRC_COLORS: [[u8; 3]; 16] = [
+    [0xff, 0xff, 0xff], [0x00, 0x00, 0x00], [0x00, 0x00, 0x7f], [0x00, 0x93, 0x00],
+    [0xff, 0x00, 0x00], [0x7f, 0x00, 0x00], [0x9c, 0x00, 0x9c], [0xfc, 0x7f, 0x00],
+    [0xff, 0xff, 0x00], [0x00, 0xfc, 0x00], [0x00, 0x93, 0x93], [0x00, 0xff, 0xff],
+    [0x00, 0x00, 0xfc], [0xff, 0x00, 0xff], [0x7f, 0x7f, 0x7f], [0xd2, 0xd2, 0xd2],
+];
+
+/// The code of the default color, for a background without a foreground.
+const DEFAULT_COLOR: u8 = 99;
+
+/// What IRC's formatting codes can display.
+#[derive(PartialEq, Debug, Default, Clone)]
+struct Formatting {
+    foreground: Option<u8>,
+    background: Option<u8>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+    reverse: bool,
+}
+
+impl Formatting {
+    fn of(appearance: &Appearance) -> Self {
+        let styles = &appearance.styles;
+
+        Formatting {
+            foreground: appearance.colors.foreground.map(irc_color),
+            background: appearance.colors.background.map(irc_color),
+            bold: styles.contains(&Style::Strong),
+            italic: styles.contains(&Style::Italic),
+            underline: styles.iter().any(Style::is_underline),
+            strikethrough: styles.contains(&Style::CrossedOut),
+            reverse: styles.contains(&Style::Reversed),
+        }
+    }
+}
+
+/// Outputs the text with mIRC's formatting codes, for the IRC messages.
+///
+/// The colors are mapped to the nearest of the 16 IRC colors, the styles that IRC doesn't have are left out.
+pub fn render_irc(spans: &Spans) -> String {
+    let mut result = String::new();
+    let mut current = Formatting::default();
+
+    for span in spans.spans.iter().filter(|it| !it.text.is_empty()) {
+        let next = Formatting::of(&span.appearance);
+        let codes = transition(&current, &next);
+
+        result.push_str(&codes);
+        // an empty bold keeps the text apart from the color code
+        if ambiguous(&codes, &span.text) {
+            result.push_str(&format!("{0}{0}", BOLD));
+        }
+        result.push_str(&span.text);
+        current = next;
+    }
+
+    result.push_str(&transition(&current, &Formatting::default()));
+
+    result
+}
+
+fn transition(from: &Formatting, to: &Formatting) -> String {
+    if from == to {
+        return String::new();
+    }
+    if *to == Formatting::default() {
+        return RESET.to_string();
+    }
+
+    let mut codes = String::new();
+
+    for (before, after, code) in [
+        (from.bold, to.bold, BOLD),
+        (from.italic, to.italic, ITALIC),
+        (from.underline, to.underline, UNDERLINE),
+        (from.strikethrough, to.strikethrough, STRIKETHROUGH),
+        (from.reverse, to.reverse, REVERSE),
+    ] {
+        if before != after {
+            codes.push(code);
+        }
+    }
+
+    if (from.foreground, from.background) != (to.foreground, to.background) {
+        match (to.foreground, to.background) {
+            (None, None) => codes.push(COLOR),
+            (Some(foreground), None) => {
+                // a color code without a background keeps the previous one
+                if from.background.is_some() {
+                    codes.push(COLOR);
+                }
+                codes.push_str(&format!("{}{:02}", COLOR, foreground));
+            }
+            (foreground, Some(background)) => {
+                codes.push_str(&format!("{}{:02},{:02}", COLOR, foreground.unwrap_or(DEFAULT_COLOR), background))
+            }
+        }
+    }
+
+    codes
+}
+
+/// A digit or a comma right after a color code would be read as a part of it.
+///
+/// The color codes always come last and their numbers always have 2 digits.
+fn ambiguous(codes: &str, text: &str) -> bool {
+    match codes.rsplit_once(COLOR) {
+        Some((_, "")) => text.starts_with(|c: char| c == ',' || c.is_ascii_digit()),
+        Some((_, numbers)) => !numbers.contains(',') && text.starts_with(','),
+        None => false,
+    }
+}
+
+fn irc_color(color: Color) -> u8 {
+    nearest_in(&color, &IRC_COLORS) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::irc::render_irc;
+    use crate::model::{Appearance, Color, Colors};
+    use crate::model::Style::{Blink, Strong, Underline};
+    use crate::span::spans_of;
+
+    fn irc(spans: Vec<(&str, Appearance)>) -> String {
+        render_irc(&spans_of(spans))
+    }
+
+    #[test]
+    fn output_the_formatting_codes_and_reset_them() {
+        let failed = Appearance::plain().with(&Colors::new_fg(Color::rgb(255, 0, 0)), &[Strong, Blink]);
+
+        assert_eq!(irc(vec!(("FAILED", failed), (" build 42", Appearance::plain()))), "\x02\x0304FAILED\x0f build 42");
+    }
+
+    #[test]
+    fn map_the_colors_to_the_nearest_irc_colors() {
+        let green_on_navy = Appearance::plain().with(&Colors::new(Color::rgb(0, 140, 10), Color::rgb(0, 0, 120)), &[]);
+        let on_gray = Appearance::plain().with(&Colors::new_bg(Color::gray(12)), &[Underline]);
+
+        assert_eq!(irc(vec!(("a", green_on_navy), ("b", on_gray))), "\x0303,02a\x1f\x0399,14b\x0f");
+    }
+
+    #[test]
+    fn keep_the_digits_apart_from_the_color_codes() {
+        let red = Appearance::plain().with(&Colors::new_fg(Color::rgb(255, 0, 0)), &[]);
+        let red_on_white = Appearance::plain().with(&Colors::new(Color::rgb(255, 0, 0), Color::rgb(255, 255, 255)), &[]);
+
+        assert_eq!(irc(vec!(("1", red.clone()), (",2", red.clone()))), "\x03041,2\x0f");
+        assert_eq!(irc(vec!(("x", red.clone()), ("5", Appearance::plain()))), "\x0304x\x0f5");
+        assert_eq!(irc(vec!(("a", red.clone()), (",b", red.with(&Colors::none(), &[Strong])))), "\x0304a\x02,b\x0f");
+        assert_eq!(irc(vec!(("x", red_on_white), (",3", red))), "\x0304,00x\x03\x0304\x02\x02,3\x0f");
+    }
+}
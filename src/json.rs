@@ -0,0 +1,124 @@
+use itertools::Itertools;
+
+use crate::html::css_color;
+use crate::model::{Color, Style};
+use crate::model::Color::{Byte, RGB};
+use crate::span::{Span, Spans};
+
+/// Outputs the spans as a JSON array, with the text, the colors and the styles of each one.
+///
+/// ```json
+/// [{"text":"error","foreground":{"index":1,"hex":"#cd0000"},"background":null,"underline":null,"styles":["bold"]}]
+/// ```
+pub fn render_json(spans: &Spans) -> String {
+    format!("[{}]", spans.spans.iter().map(span).join(","))
+}
+
+fn span(span: &Span) -> String {
+    let colors = &span.appearance.colors;
+
+    format!(
+        "{{\"text\":{},\"foreground\":{},\"background\":{},\"underline\":{},\"styles\":[{}]}}",
+        string(&span.text),
+        color(colors.foreground),
+        color(colors.background),
+        color(colors.underline),
+        span.appearance.styles.iter().map(|it| string(&style_name(it))).join(","),
+    )
+}
+
+/// The palette colors keep their index, because their actual color depends on the terminal's theme.
+fn color(color: Option<Color>) -> String {
+    match color {
+        None => "null".to_string(),
+        Some(it @ Byte(index)) => format!("{{\"index\":{},\"hex\":\"{}\"}}", index, css_color(it)),
+        Some(it @ RGB { .. }) => format!("{{\"hex\":\"{}\"}}", css_color(it)),
+    }
+}
+
+/// The main name of the style in the format.
+pub fn style_name(style: &Style) -> String {
+    match style {
+        Style::Absent => "reset",
+        Style::Strong => "bold",
+        Style::Dim => "dim",
+        Style::Italic => "italic",
+        Style::Underline => "underline",
+        Style::DoubleUnderline => "double",
+        Style::CurlyUnderline => "curly",
+        Style::DottedUnderline => "dotted",
+        Style::DashedUnderline => "dashed",
+        Style::DoublyUnderlined => "doubly-underlined",
+        Style::Blink => "blink",
+        Style::RapidBlink => "rapid-blink",
+        Style::Reversed => "reversed",
+        Style::Hidden => "hidden",
+        Style::CrossedOut => "crossed-out",
+        Style::AlternativeFont(font) => return format!("font{}", font),
+        Style::Fraktur => "fraktur",
+        Style::Framed => "framed",
+        Style::Encircled => "encircled",
+        Style::Overline => "overline",
+        Style::Superscript => "superscript",
+        Style::Subscript => "subscript",
+    }.to_string()
+}
+
+/// The text as a JSON string, with its quotes.
+pub fn string(text: &str) -> String {
+    let mut result = String::from('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            _ if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            _ => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::{render_json, string};
+    use crate::model::{Appearance, Color, Colors};
+    use crate::model::Style::{AlternativeFont, CurlyUnderline, Strong};
+    use crate::span::spans_of;
+
+    fn json(spans: Vec<(&str, Appearance)>) -> String {
+        render_json(&spans_of(spans))
+    }
+
+    #[test]
+    fn output_the_text_colors_and_styles_of_each_span() {
+        let styled = Appearance::plain().with(
+            &Colors { foreground: Some(Color::red()), background: Some(Color::rgb(1, 2, 3)), underline: None },
+            &[Strong, CurlyUnderline, AlternativeFont(2)],
+        );
+
+        assert_eq!(
+            json(vec!(("error", styled), (": disk full", Appearance::plain()))),
+            [
+                "[{\"text\":\"error\",\"foreground\":{\"index\":1,\"hex\":\"#cd0000\"},\"background\":{\"hex\":\"#010203\"},",
+                "\"underline\":null,\"styles\":[\"bold\",\"curly\",\"font2\"]},",
+                "{\"text\":\": disk full\",\"foreground\":null,\"background\":null,\"underline\":null,\"styles\":[]}]",
+            ].concat()
+        );
+    }
+
+    #[test]
+    fn an_empty_output_is_an_empty_array() {
+        assert_eq!(json(vec!()), "[]");
+    }
+
+    #[test]
+    fn escape_the_strings() {
+        assert_eq!(string("say \"hi\"\\\n\t\x1b"), "\"say \\\"hi\\\"\\\\\\n\\t\\u001b\"");
+    }
+}
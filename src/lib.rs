@@ -1,6 +1,25 @@
-mod model;
+pub mod model;
+mod color_space;
+mod css;
+pub mod config;
+mod downsample;
 mod parser;
 mod helpers;
 pub mod cecho;
+mod span;
+pub mod strip;
 mod writer;
+mod html;
+mod svg;
+mod json;
+mod pango;
+mod polybar;
+mod prompt;
+mod tmux;
+mod irc;
+mod discord;
+pub mod ansi;
+pub mod reverse;
+mod debug;
+pub mod check;
 
@@ -1,12 +1,24 @@
 use std::env;
-use cecho::cecho::cecho;
+use std::io::{stdin, stdout};
+use cecho::cecho::cecho_with_config;
+use cecho::check::check_formats;
+use cecho::config::{Config, Mode};
+use cecho::reverse::reverse_filter;
+use cecho::strip::strip_filter;
 
 fn main() {
     let mut arguments: Vec<String> = Vec::new();
     for a in env::args().skip(1) /* skip the process name */ {
         arguments.push(a)
     }
-    let result = cecho(arguments);
+
+    let result = Config::from_env().and_then(|config| match config.mode {
+        Mode::Format => cecho_with_config(arguments, &config),
+        // the filter writes as it reads, there's nothing left to print
+        Mode::Strip => strip_filter(&arguments, stdin().lock(), stdout().lock()).map(|_| String::new()),
+        Mode::Reverse => reverse_filter(&arguments, stdin().lock(), stdout().lock()).map(|_| String::new()),
+        Mode::Check => check_formats(&arguments, &config),
+    });
 
     match result {
         Err(m) => {
@@ -40,6 +52,18 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn fail_without_crashing_when_an_argument_is_missing() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("cecho")?;
+
+        cmd.arg("{} {}").arg("a");
+        cmd.assert()
+            .code(1)
+            .stderr(predicate::str::contains("The specifier #2 refers to the missing argument 2"));
+
+        Ok(())
+    }
+
     #[test]
     fn print_any_string_by_omitting_the_format() -> Result<(), Box<dyn std::error::Error>> {
         let mut cmd = Command::cargo_bin("cecho")?;
@@ -76,6 +100,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn print_plain_text_when_no_color_is_set() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("cecho")?;
+
+        cmd.env_remove("CECHO_COLOR").env_remove("CLICOLOR_FORCE").env("NO_COLOR", "1");
+        cmd.arg("{#red}").arg("value");
+        cmd.assert()
+            .success()
+            .stdout(predicate::eq("value"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn print_colors_when_forced_even_if_the_output_is_not_a_terminal() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("cecho")?;
+
+        cmd.env_remove("CECHO_COLOR").env_remove("NO_COLOR").env("CLICOLOR_FORCE", "1");
+        cmd.env("CECHO_COLOR_DEPTH", "16");
+        cmd.arg("{#red}").arg("value");
+        cmd.assert()
+            .success()
+            .stdout(predicate::eq("\x1b[31mvalue\x1b[0m"));
+
+        Ok(())
+    }
+
     #[test]
     fn print_literal_brackets() -> Result<(), Box<dyn std::error::Error>> {
         let mut cmd = Command::cargo_bin("cecho")?;
@@ -87,4 +138,58 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn print_a_trailing_newline_on_demand() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("cecho")?;
+
+        cmd.env("CECHO_NEWLINE", "1").env("CECHO_COLOR", "never");
+        cmd.arg("").arg("value");
+        cmd.assert()
+            .success()
+            .stdout(predicate::eq("value\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn strip_the_escape_sequences_from_the_standard_input() -> Result<(), Box<dyn std::error::Error>> {
+        // the std Command can't write to the standard input
+        let mut cmd = assert_cmd::Command::cargo_bin("cecho")?;
+
+        cmd.env("CECHO_MODE", "strip");
+        cmd.write_stdin("\x1b[1;31merror:\x1b[0m disk full\n");
+        cmd.assert()
+            .success()
+            .stdout(predicate::eq("error: disk full\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn convert_the_standard_input_to_formats() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = assert_cmd::Command::cargo_bin("cecho")?;
+
+        cmd.env("CECHO_MODE", "reverse");
+        cmd.write_stdin("\x1b[1;31merror:\x1b[0m disk full\n");
+        cmd.assert()
+            .success()
+            .stdout(predicate::eq("{\"error:\" #r!s} disk full\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_the_formats_and_fail_on_a_problem() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("cecho")?;
+
+        cmd.env("CECHO_MODE", "check");
+        cmd.arg("{#red}ok{/}").arg("{!ss}");
+        cmd.assert()
+            .failure()
+            .stdout(predicate::eq(""))
+            .stderr(predicate::eq("'{!ss}': warning: the specifier #1 has the style bold more than once\n"));
+
+        Ok(())
+    }
 }
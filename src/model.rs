@@ -1,8 +1,9 @@
+use crate::color_space::{bytes_to_srgb, hsl_to_srgb, srgb_to_bytes, srgb_to_hsl};
 use crate::model::Color::{Byte, RGB};
 use crate::model::Part::{Literal, Specification};
 use crate::model::Text::{AllArgs, Indexed, Positional};
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone)]
 #[derive(Debug)]
 pub enum Part {
     Literal(String),
@@ -10,6 +11,20 @@ pub enum Part {
         text: Text,
         color: Colors,
         styles: Vec<Style>,
+        /// Keeps the colors and styles after the text instead of resetting them, like `{#red!preserve}`.
+        preserve: bool,
+    },
+    /// Applies the colors and styles to everything until the matching `Close`, like `{#red}`.
+    Open {
+        color: Colors,
+        styles: Vec<Style>,
+    },
+    /// Restores what was in effect before the matching `Open`, like `{/}` or `{/#red}`.
+    ///
+    /// Holds the colors and styles of the `Open` that it closes.
+    Close {
+        color: Colors,
+        styles: Vec<Style>,
     },
 }
 
@@ -18,64 +33,87 @@ impl Part {
         Literal(text.to_string())
     }
     pub fn positional() -> Self {
-        Specification { text: Positional, color: Colors::none(), styles: vec!() }
+        Specification { text: Positional, color: Colors::none(), styles: vec!(), preserve: false }
     }
     pub fn all_args() -> Self {
-        Specification { text: AllArgs(" ".to_string()), color: Colors::none(), styles: vec!() }
+        Specification { text: AllArgs(" ".to_string()), color: Colors::none(), styles: vec!(), preserve: false }
     }
     pub fn all_args_custom_separator(separator: &str) -> Self {
-        Specification { text: AllArgs(separator.to_string()), color: Colors::none(), styles: vec!() }
+        Specification { text: AllArgs(separator.to_string()), color: Colors::none(), styles: vec!(), preserve: false }
     }
     pub fn positional_color(color: Color) -> Self {
-        Specification { text: Positional, color: Colors::new_fg(color), styles: vec!() }
+        Specification { text: Positional, color: Colors::new_fg(color), styles: vec!(), preserve: false }
     }
     pub fn positional_background_color(color: Color) -> Self {
-        Specification { text: Positional, color: Colors::new_bg(color), styles: vec!() }
+        Specification { text: Positional, color: Colors::new_bg(color), styles: vec!(), preserve: false }
     }
     pub fn positional_style(style: Style) -> Self {
-        Specification { text: Positional, color: Colors::none(), styles: vec!(style) }
+        Specification { text: Positional, color: Colors::none(), styles: vec!(style), preserve: false }
     }
     pub fn positional_styles(styles: Vec<Style>) -> Self {
-        Specification { text: Positional, color: Colors::none(), styles: styles }
+        Specification { text: Positional, color: Colors::none(), styles, preserve: false }
     }
     pub const fn indexed(index: usize) -> Self {
-        Specification { text: Indexed(index), color: Colors::none(), styles: vec!() }
+        Specification { text: Indexed(index), color: Colors::none(), styles: vec!(), preserve: false }
     }
     pub const fn indexed_color(index: usize, color: Colors) -> Self {
-        Specification { text: Indexed(index), color, styles: vec!() }
+        Specification { text: Indexed(index), color, styles: vec!(), preserve: false }
+    }
+    pub const fn open(color: Colors, styles: Vec<Style>) -> Self {
+        Part::Open { color, styles }
+    }
+    pub const fn close(color: Colors, styles: Vec<Style>) -> Self {
+        Part::Close { color, styles }
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum Text {
     Positional,
     Indexed(usize),
     AllArgs(String),
+    /// Fixed text from the format itself, like `{"WARNING" #Y}`
+    Literal(String),
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct Colors {
     pub foreground: Option<Color>,
     pub background: Option<Color>,
+    pub underline: Option<Color>,
 }
 
 impl Colors {
+    pub fn is_none(&self) -> bool {
+        self.foreground.is_none() && self.background.is_none() && self.underline.is_none()
+    }
+    /// These colors, replaced by the other colors where they are specified.
+    pub fn overlaid_with(&self, other: &Colors) -> Self {
+        Colors {
+            foreground: other.foreground.or(self.foreground),
+            background: other.background.or(self.background),
+            underline: other.underline.or(self.underline),
+        }
+    }
     pub const fn none() -> Self {
-        Colors { foreground: None, background: None }
+        Colors { foreground: None, background: None, underline: None }
     }
     pub const fn new(foreground: Color, background: Color) -> Self {
-        Colors { foreground: Some(foreground), background: Some(background) }
+        Colors { foreground: Some(foreground), background: Some(background), underline: None }
     }
     pub const fn new_fg(foreground: Color) -> Self {
-        Colors { foreground: Some(foreground), background: None }
+        Colors { foreground: Some(foreground), background: None, underline: None }
     }
     pub const fn new_bg(background: Color) -> Self {
-        Colors { foreground: None, background: Some(background) }
+        Colors { foreground: None, background: Some(background), underline: None }
+    }
+    pub const fn new_underline(underline: Color) -> Self {
+        Colors { foreground: None, background: None, underline: Some(underline) }
     }
 }
 
-#[derive(PartialEq)]
-#[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Debug, Copy, Clone)]
 pub enum Color {
     Byte(u8),
     // ANSI color set
@@ -94,6 +132,13 @@ const GREEN: u8 = 0b0010;
 const BLUE: u8 = 0b0100;
 const BRIGHT: u8 = 0b1000;
 
+// xterm's default values for the 16 named colors, the actual colors depend on the terminal's theme
+const XTERM_NAMED_COLORS: [u32; 16] = [
+    0x000000, 0xcd0000, 0x00cd00, 0xcdcd00, 0x0000ee, 0xcd00cd, 0x00cdcd, 0xe5e5e5,
+    0x7f7f7f, 0xff0000, 0x00ff00, 0xffff00, 0x5c5cff, 0xff00ff, 0x00ffff, 0xffffff,
+];
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
 impl Color {
     pub const fn black() -> Self { Byte(0) }
     pub const fn red() -> Self { Byte(RED) }
@@ -105,6 +150,7 @@ impl Color {
     pub const fn white() -> Self { Byte(RED | GREEN | BLUE) }
 
     // because physics, lol XD
+    #[allow(clippy::identity_op)]
     pub const fn bright_black() -> Self { Byte(BRIGHT | 0) }
     pub const fn bright_red() -> Self { Byte(BRIGHT | RED) }
     pub const fn bright_green() -> Self { Byte(BRIGHT | GREEN) }
@@ -114,6 +160,12 @@ impl Color {
     pub const fn bright_cyan() -> Self { Byte(BRIGHT | GREEN | BLUE) }
     pub const fn bright_white() -> Self { Byte(BRIGHT | RED | GREEN | BLUE) }
 
+    /// A color from the 256 colors palette: 0-15 are the named colors, 16-231 the 6x6x6 cube and 232-255 the grayscale ramp.
+    pub const fn palette(index: u8) -> Self { Byte(index) }
+
+    /// One of the 24 steps of the grayscale ramp, from the darkest (0) to the lightest (23).
+    pub const fn gray(level: u8) -> Self { Byte(232 + level) }
+
     pub fn u32_rgb(value: u32) -> Self {
         RGB {
             red: ((value >> 16) & 0xff) as u8,
@@ -123,6 +175,49 @@ impl Color {
     }
     pub fn rgb(red: u8, green: u8, blue: u8) -> Self { RGB { red, green, blue } }
 
+    fn from_srgb(srgb: [f64; 3]) -> Self {
+        let [red, green, blue] = srgb_to_bytes(srgb);
+        RGB { red, green, blue }
+    }
+
+    /// Changes the HSL lightness by `amount`, a negative amount darkens the color.
+    pub fn lighten(&self, amount: f64) -> Self {
+        let [hue, saturation, lightness] = srgb_to_hsl(bytes_to_srgb(self.to_rgb()));
+        Color::from_srgb(hsl_to_srgb(hue, saturation, (lightness + amount).clamp(0.0, 1.0)))
+    }
+
+    /// The color on the opposite side of the color wheel.
+    pub fn complement(&self) -> Self {
+        let [hue, saturation, lightness] = srgb_to_hsl(bytes_to_srgb(self.to_rgb()));
+        Color::from_srgb(hsl_to_srgb(hue + 180.0, saturation, lightness))
+    }
+
+    /// Mixes 2 colors, `weight` is the proportion of this color, between 0 and 1.
+    pub fn mix(&self, other: &Color, weight: f64) -> Self {
+        let this = bytes_to_srgb(self.to_rgb());
+        let that = bytes_to_srgb(other.to_rgb());
+        Color::from_srgb([0, 1, 2].map(|i| this[i] * weight + that[i] * (1.0 - weight)))
+    }
+
+    /// The red, green and blue components, using xterm's default palette for the palette colors.
+    pub fn to_rgb(&self) -> [u8; 3] {
+        match *self {
+            Byte(b) if b < 16 => {
+                let value = XTERM_NAMED_COLORS[b as usize];
+                [(value >> 16) as u8, (value >> 8) as u8, value as u8]
+            }
+            Byte(b) if b < 232 => {
+                let cube = (b - 16) as usize;
+                [CUBE_LEVELS[cube / 36], CUBE_LEVELS[cube / 6 % 6], CUBE_LEVELS[cube % 6]]
+            }
+            Byte(b) => {
+                let level = 8 + 10 * (b - 232);
+                [level, level, level]
+            }
+            RGB { red, green, blue } => [red, green, blue],
+        }
+    }
+
     pub fn escape_code(&self) -> String {
         let mut code = String::new();
 
@@ -130,8 +225,11 @@ impl Color {
             Byte(b) => {
                 if b < &8 {
                     code.push_str(&(30 + b).to_string());
+                } else if b < &16 {
+                    code.push_str(&(90 + b - 8).to_string());
                 } else {
-                    code.push_str(&(82 + b).to_string());
+                    code.push_str("38;5;");
+                    code.push_str(&b.to_string());
                 }
             }
             RGB { red, green, blue } => {
@@ -147,6 +245,14 @@ impl Color {
         code
     }
 
+    /// The underline color, distinct from the text color in the terminals that support it.
+    pub fn as_ansi_underline_escape_code(&self) -> String {
+        match self {
+            Byte(b) => format!("58;5;{}", b),
+            RGB { red, green, blue } => format!("58;2;{};{};{}", red, green, blue),
+        }
+    }
+
     pub fn as_ansi_background_escape_code(&self) -> String {
         let mut code = String::new();
 
@@ -154,8 +260,11 @@ impl Color {
             Byte(b) => {
                 if b < &8 {
                     code.push_str(&(40 + b).to_string());
+                } else if b < &16 {
+                    code.push_str(&(100 + b - 8).to_string());
                 } else {
-                    code.push_str(&(92 + b).to_string());
+                    code.push_str("48;5;");
+                    code.push_str(&b.to_string());
                 }
             }
             RGB { red, green, blue } => {
@@ -174,13 +283,116 @@ impl Color {
 
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub enum Style {
-    Absent = 0,
-    Strong = 1,
-    Dim = 2,
-    Italic = 3,
-    Underline = 4,
-    Blink = 5,
-    Reversed = 7,
-    Hidden = 8,
-    CrossedOut = 9,
+    Absent,
+    Strong,
+    Dim,
+    Italic,
+    Underline,
+    DoubleUnderline,
+    CurlyUnderline,
+    DottedUnderline,
+    DashedUnderline,
+    DoublyUnderlined,
+    Blink,
+    RapidBlink,
+    Reversed,
+    Hidden,
+    CrossedOut,
+    /// One of the 9 alternative fonts, from 1 to 9, or the primary font 0
+    AlternativeFont(u8),
+    Fraktur,
+    Framed,
+    Encircled,
+    Overline,
+    Superscript,
+    Subscript,
+}
+
+/// The colors and styles in effect at some point of the output.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Appearance {
+    pub colors: Colors,
+    pub styles: Vec<Style>,
+}
+
+impl Appearance {
+    pub const fn plain() -> Self {
+        Appearance { colors: Colors::none(), styles: vec!() }
+    }
+
+    pub fn is_plain(&self) -> bool {
+        self.colors.is_none() && self.styles.is_empty()
+    }
+
+    /// This appearance with more colors and styles on top.
+    ///
+    /// `Absent` resets everything before the other colors and styles apply.
+    pub fn with(&self, colors: &Colors, styles: &[Style]) -> Self {
+        let base = if styles.contains(&Style::Absent) { Appearance::plain() } else { self.clone() };
+        let mut combined = base.styles;
+        styles.iter()
+            .filter(|it| **it != Style::Absent)
+            .for_each(|it| if !combined.contains(it) { combined.push(*it) });
+
+        Appearance { colors: base.colors.overlaid_with(colors), styles: combined }
+    }
+}
+
+impl Style {
+    /// The SGR parameter that enables the style.
+    pub fn code(&self) -> String {
+        match self {
+            Style::Absent => "0",
+            Style::Strong => "1",
+            Style::Dim => "2",
+            Style::Italic => "3",
+            Style::Underline => "4",
+            // the extended underlines use sub-parameters, supported by kitty, WezTerm, iTerm2, VTE, ...
+            Style::DoubleUnderline => "4:2",
+            Style::CurlyUnderline => "4:3",
+            Style::DottedUnderline => "4:4",
+            Style::DashedUnderline => "4:5",
+            Style::DoublyUnderlined => "21",
+            Style::Blink => "5",
+            Style::RapidBlink => "6",
+            Style::Reversed => "7",
+            Style::Hidden => "8",
+            Style::CrossedOut => "9",
+            Style::AlternativeFont(font) => return (10 + font).to_string(),
+            Style::Fraktur => "20",
+            Style::Framed => "51",
+            Style::Encircled => "52",
+            Style::Overline => "53",
+            Style::Superscript => "73",
+            Style::Subscript => "74",
+        }.to_string()
+    }
+
+    /// Any of the underlines, that a single SGR parameter turns off.
+    pub fn is_underline(&self) -> bool {
+        matches!(
+            self,
+            Style::Underline | Style::DoubleUnderline | Style::CurlyUnderline | Style::DottedUnderline
+            | Style::DashedUnderline | Style::DoublyUnderlined
+        )
+    }
+
+    /// The SGR parameter that disables the style, shared by the styles that go together.
+    pub fn off_code(&self) -> Option<&'static str> {
+        match self {
+            Style::Absent => None,
+            Style::Strong | Style::Dim => Some("22"),
+            Style::Italic | Style::Fraktur => Some("23"),
+            Style::Underline | Style::DoubleUnderline | Style::CurlyUnderline | Style::DottedUnderline
+            | Style::DashedUnderline | Style::DoublyUnderlined => Some("24"),
+            Style::Blink | Style::RapidBlink => Some("25"),
+            Style::Reversed => Some("27"),
+            Style::Hidden => Some("28"),
+            Style::CrossedOut => Some("29"),
+            Style::AlternativeFont(_) => Some("10"),
+            Style::Framed | Style::Encircled => Some("54"),
+            Style::Overline => Some("55"),
+            Style::Superscript | Style::Subscript => Some("75"),
+        }
+    }
 }
@@ -0,0 +1,153 @@
+use crate::html::{css_color, escape};
+use crate::json::string;
+use crate::model::{Appearance, Style};
+use crate::span::Spans;
+
+/// Outputs the text as Pango markup, for the status bars like i3blocks and waybar.
+pub fn render_pango(spans: &Spans) -> String {
+    let mut result = String::new();
+    let mut pieces = spans.spans.iter().filter(|it| !it.text.is_empty()).peekable();
+
+    while let Some(first) = pieces.next() {
+        // the adjacent pieces that look the same share their span
+        let mut text = first.text.clone();
+        while let Some(next) = pieces.next_if(|it| it.appearance == first.appearance) {
+            text.push_str(&next.text);
+        }
+
+        let attributes = attributes(&first.appearance);
+
+        if attributes.is_empty() {
+            result.push_str(&escape(&text));
+        } else {
+            result.push_str(&format!("<span {}>{}</span>", attributes.join(" "), escape(&text)));
+        }
+    }
+
+    result
+}
+
+/// Outputs a block of the i3bar protocol, with the text as Pango markup, for i3bar and swaybar.
+///
+/// ```json
+/// {"full_text":"<span foreground=\"#cd0000\">CPU</span> 99%","markup":"pango"}
+/// ```
+pub fn render_i3bar(spans: &Spans) -> String {
+    format!("{{\"full_text\":{},\"markup\":\"pango\"}}", string(&render_pango(spans)))
+}
+
+/// The Pango attributes that display the colors and styles.
+///
+/// Blinking, framing and the alternative fonts have no equivalent and are left out.
+/// The reversed colors are only swapped when they are both known.
+fn attributes(appearance: &Appearance) -> Vec<String> {
+    let styles = &appearance.styles;
+    let has = |style: Style| styles.contains(&style);
+    let mut attributes = Vec::new();
+
+    let colors = &appearance.colors;
+    let (foreground, background) = match (has(Style::Reversed), colors.foreground, colors.background) {
+        (true, Some(foreground), Some(background)) => (Some(background), Some(foreground)),
+        _ => (colors.foreground, colors.background),
+    };
+
+    if let Some(color) = foreground {
+        attributes.push(format!("foreground=\"{}\"", css_color(color)));
+    }
+    if let Some(color) = background {
+        attributes.push(format!("background=\"{}\"", css_color(color)));
+    }
+    if has(Style::Strong) {
+        attributes.push("weight=\"bold\"".to_string());
+    }
+    if has(Style::Hidden) {
+        attributes.push("alpha=\"1\"".to_string());
+    } else if has(Style::Dim) {
+        attributes.push("alpha=\"50%\"".to_string());
+    }
+    if has(Style::Italic) {
+        attributes.push("style=\"italic\"".to_string());
+    }
+
+    let underline = styles.iter().find_map(|it| match it {
+        Style::Underline | Style::DottedUnderline | Style::DashedUnderline => Some("single"),
+        Style::DoubleUnderline | Style::DoublyUnderlined => Some("double"),
+        Style::CurlyUnderline => Some("error"),
+        _ => None,
+    });
+
+    if let Some(kind) = underline {
+        attributes.push(format!("underline=\"{}\"", kind));
+    }
+    if let Some(color) = colors.underline {
+        attributes.push(format!("underline_color=\"{}\"", css_color(color)));
+    }
+    if has(Style::Overline) {
+        attributes.push("overline=\"single\"".to_string());
+    }
+    if has(Style::CrossedOut) {
+        attributes.push("strikethrough=\"true\"".to_string());
+    }
+    if has(Style::Superscript) {
+        attributes.push("baseline_shift=\"superscript\" font_scale=\"superscript\"".to_string());
+    }
+    if has(Style::Subscript) {
+        attributes.push("baseline_shift=\"subscript\" font_scale=\"subscript\"".to_string());
+    }
+    if has(Style::Fraktur) {
+        attributes.push("font_family=\"fantasy\"".to_string());
+    }
+
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Appearance, Color, Colors};
+    use crate::model::Style::{CurlyUnderline, Dim, Reversed, Strong};
+    use crate::pango::{render_i3bar, render_pango};
+    use crate::span::spans_of;
+
+    fn pango(spans: Vec<(&str, Appearance)>) -> String {
+        render_pango(&spans_of(spans))
+    }
+
+    #[test]
+    fn wrap_the_styled_text_in_spans() {
+        let red_bold = Appearance::plain().with(&Colors::new_fg(Color::red()), &[Strong, Dim]);
+
+        assert_eq!(
+            pango(vec!(("CPU", red_bold), (" 99% & more", Appearance::plain()))),
+            "<span foreground=\"#cd0000\" weight=\"bold\" alpha=\"50%\">CPU</span> 99% &amp; more"
+        );
+    }
+
+    #[test]
+    fn map_the_underlines() {
+        let typo = Appearance::plain().with(&Colors::new_underline(Color::rgb(255, 0, 0)), &[CurlyUnderline]);
+
+        assert_eq!(
+            pango(vec!(("typo", typo))),
+            "<span underline=\"error\" underline_color=\"#ff0000\">typo</span>"
+        );
+    }
+
+    #[test]
+    fn swap_the_known_colors_when_reversed() {
+        let both = Appearance::plain().with(&Colors::new(Color::red(), Color::black()), &[Reversed]);
+        let one = Appearance::plain().with(&Colors::new_fg(Color::red()), &[Reversed]);
+
+        assert_eq!(pango(vec!(("a", both))), "<span foreground=\"#000000\" background=\"#cd0000\">a</span>");
+        assert_eq!(pango(vec!(("b", one))), "<span foreground=\"#cd0000\">b</span>");
+    }
+
+    #[test]
+    fn wrap_the_markup_in_an_i3bar_block() {
+        let red = Appearance::plain().with(&Colors::new_fg(Color::red()), &[]);
+
+        assert_eq!(
+            render_i3bar(&spans_of(vec!(("CPU", red), (" \"99%\"", Appearance::plain())))),
+            r##"{"full_text":"<span foreground=\"#cd0000\">CPU</span> &quot;99%&quot;","markup":"pango"}"##
+        );
+    }
+}
@@ -1,25 +1,44 @@
 use std::str::Chars;
 
 use lazy_static::lazy_static;
-use regex::{Match, Regex};
+use regex::Regex;
 
+use crate::css::{color_function, fraction, named_color};
 use crate::model::{Color, Colors, Part, Style, Text};
 use crate::model::Part::{Literal, Specification};
-use crate::model::Style::{Absent, Blink, Strong, Dim, Hidden, Reversed, Italic, CrossedOut, Underline};
+use crate::model::Style::{Absent, AlternativeFont, Blink, Strong, Dim, Hidden, Reversed, Italic, CrossedOut, Underline, DoubleUnderline, CurlyUnderline, DottedUnderline, DashedUnderline, DoublyUnderlined, RapidBlink, Fraktur, Framed, Encircled, Overline, Superscript, Subscript};
 use crate::model::Text::{AllArgs, Indexed, Positional};
 use crate::parser::ParserMode::{AllArgsMode, ColorMode, IndexMode, StyleMode};
 
 lazy_static! {
-    static ref HEX_COLOR : Regex = Regex::new(r#"^(?<code>[[:xdigit:]]{6})$"#).unwrap();
-    static ref DEC_COLOR : Regex = Regex::new(r#"^(?<rgb>rgb\((?<red>[[:digit:]]{1,3}),(?<green>[[:digit:]]{1,3}),(?<blue>[[:digit:]]{1,3})\))$"#).unwrap();
-    static ref COLOR_PARTS_REGEX : Regex = Regex::new("^\\s*(?<fg>[^/]+)?\\s*(/\\s*(?<bg>.+))?\\s*$").unwrap();
+    static ref HEX_COLOR : Regex = Regex::new(r#"^(?<code>[[:xdigit:]]{6}|[[:xdigit:]]{3})$"#).unwrap();
+    static ref FUNCTION_COLOR : Regex = Regex::new(r#"^(?<name>[[:alpha:]]+)\s*\((?<arguments>.*)\)$"#).unwrap();
+    // three digits are a #rgb shorthand, the indexes above 99 need the `c`
+    static ref PALETTE_COLOR : Regex = Regex::new(r#"^(c(?<explicit>[[:digit:]]{3})|(?<index>[[:digit:]]{1,2}))$"#).unwrap();
+    static ref ANSI_COLOR : Regex = Regex::new(r#"^ansi\(\s*(?<index>[[:digit:]]{1,3})\s*\)$"#).unwrap();
+    static ref GRAY_COLOR : Regex = Regex::new(r#"^gr[ae]y(?<level>[[:digit:]]{1,2})$"#).unwrap();
     static ref ALL_ARGS_REGEX : Regex = Regex::new("^@\\|(?<separator>.*)\\|$").unwrap();
 }
-pub fn parse_format(format: &String) -> Result<Vec<Part>, String> {
-    return parse_format_in_default_mode(&mut format.chars());
+pub fn parse_format(format: &str) -> Result<Vec<Part>, String> {
+    parse_format_with_all_errors(format).map_err(|mut errors| errors.remove(0))
 }
 
-fn parse_format_in_default_mode<'a, 'b>(chars: &'a mut Chars<'a>) -> Result<Vec<Part>, String> {
+/// Goes on after the errors of a specifier or an escape sequence, to report all of them at once.
+///
+/// It stops at the first nested or imbalanced specifier, after which the braces can't be matched anymore.
+/// The closing tags are only matched when there is no other error.
+pub fn parse_format_with_all_errors(format: &str) -> Result<Vec<Part>, Vec<String>> {
+    let mut errors = Vec::new();
+    let parts = parse_format_in_default_mode(&mut format.chars(), &mut errors);
+
+    if errors.is_empty() {
+        resolve_scopes(parts).map_err(|it| vec!(it))
+    } else {
+        Err(errors)
+    }
+}
+
+fn parse_format_in_default_mode<'a>(chars: &'a mut Chars<'a>, errors: &mut Vec<String>) -> Vec<Part> {
     let mut specs: Vec<Part> = Vec::new();
     let mut escaped = false;
     let mut so_far = String::new();
@@ -33,10 +52,15 @@ fn parse_format_in_default_mode<'a, 'b>(chars: &'a mut Chars<'a>) -> Result<Vec<
                     specs.push(Literal(so_far.to_string()));
                 }
                 so_far = String::new();
-                let next = parse_format_in_spec_mode(chars);
-                match next {
-                    Err(e) => return Err(e),
-                    Ok(it) => specs.push(it),
+                match read_spec(chars) {
+                    Err(e) => {
+                        errors.push(e);
+                        return specs;
+                    }
+                    Ok(spec) => match interpret_spec(&spec) {
+                        Err(e) => errors.push(e),
+                        Ok(it) => specs.push(it),
+                    },
                 }
             }
             '\\' => if escaped {
@@ -46,18 +70,9 @@ fn parse_format_in_default_mode<'a, 'b>(chars: &'a mut Chars<'a>) -> Result<Vec<
                 escaped = true;
             }
             _ => if escaped {
-                match c {
-                    'a' => so_far.push('\x07'),
-                    'b' => so_far.push('\x08'),
-                    't' => so_far.push('\x09'),
-                    'n' => so_far.push('\n'),
-                    'v' => so_far.push('\x0b'),
-                    'f' => so_far.push('\x0c'),
-                    'r' => so_far.push('\x0d'),
-                    'e' => so_far.push('\x1b'),
-                    '}' => so_far.push('}'),
-                    '\\' => panic!("This should never happen, \\ is handled above."),
-                    _ => panic!("Invalid escape sequence: \\{}", c),
+                match unescape(c) {
+                    Some(it) => so_far.push(it),
+                    None => errors.push(format!("Invalid escape sequence: \\{}", c)),
                 }
             } else {
                 so_far.push(c);
@@ -72,36 +87,133 @@ fn parse_format_in_default_mode<'a, 'b>(chars: &'a mut Chars<'a>) -> Result<Vec<
         specs.push(Literal(so_far.to_string()));
     }
 
-    Ok(specs)
+    specs
+}
+
+/// The character that an escape sequence like `\n` stands for.
+fn unescape(c: char) -> Option<char> {
+    match c {
+        'a' => Some('\x07'),
+        'b' => Some('\x08'),
+        't' => Some('\x09'),
+        'n' => Some('\n'),
+        'v' => Some('\x0b'),
+        'f' => Some('\x0c'),
+        'r' => Some('\x0d'),
+        'e' => Some('\x1b'),
+        '\\' | '{' | '}' | '"' | '\'' => Some(c),
+        _ => None,
+    }
+}
+
+/// A quote opens a quoted literal only at the start of a word, so that `{@|'|}` is still a separator.
+fn opens_quote(c: char, before: &str) -> bool {
+    matches!(c, '"' | '\'') && (before.is_empty() || before.ends_with(char::is_whitespace))
 }
 
-fn parse_format_in_spec_mode<'a, 'b>(chars: &mut Chars) -> Result<Part, String> {
+/// The text between the braces, up to the closing one.
+fn read_spec(chars: &mut Chars) -> Result<String, String> {
     let mut so_far = String::new();
+    // the braces are ordinary characters inside a quoted literal
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
 
-    while let Some(c) = chars.next() {
+    for c in chars.by_ref() {
         match c {
+            _ if escaped => escaped = false,
+            '\\' if quote.is_some() => escaped = true,
+            _ if quote == Some(c) => quote = None,
+            _ if quote.is_some() => {}
+            _ if opens_quote(c, &so_far) => quote = Some(c),
             '{' => {
                 return Err("Can't nest specifiers".to_string());
             }
-            '}' => {
-                return match so_far.as_ref() {
-                    "" => {
-                        Ok(Part::positional())
-                    }
-                    _ => {
-                        parse_spec(so_far.as_str())
-                    }
-                };
+            '}' => return Ok(so_far),
+            _ => {}
+        }
+
+        so_far.push(c);
+    }
+
+    match quote {
+        Some(quote) => Err(format!("The quoted text is missing its closing {}", quote)),
+        None => Err("The specifiers are imbalanced: missing }".to_string()),
+    }
+}
+
+fn interpret_spec(spec: &str) -> Result<Part, String> {
+    if let Some(closing) = spec.trim_start().strip_prefix('/') {
+        return parse_closing_tag(closing);
+    }
+
+    match spec {
+        "" => Ok(Part::positional()),
+        _ => parse_spec(spec),
+    }
+}
+
+fn parse_closing_tag(closing: &str) -> Result<Part, String> {
+    match parse_spec(closing)? {
+        Specification { text: Positional, color, styles, preserve: false } => Ok(Part::close(color, styles)),
+        _ => Err(format!("A closing tag can only contain colors and styles: '{{/{}}}'", closing)),
+    }
+}
+
+/// Pairs the closing tags with the specifiers that they close.
+///
+/// A specifier that has colors or styles but no text, like `{#red}`, opens a scope when a closing tag follows it.
+/// Otherwise, it stays a positional placeholder.
+/// `{/}` closes the innermost scope, `{/#red}` or `{/!bold}` close the innermost scope that has this color or style.
+fn resolve_scopes(mut parts: Vec<Part>) -> Result<Vec<Part>, String> {
+    let mut candidates: Vec<usize> = Vec::new();
+
+    for i in 0..parts.len() {
+        match &parts[i] {
+            Specification { text: Positional, color, styles, preserve: false } if !color.is_none() || !styles.is_empty() => {
+                candidates.push(i);
             }
-            _ => {
-                so_far.push(c);
+            Part::Close { color, styles } => {
+                let closed = candidates.iter().rposition(|it| closes(&parts[*it], color, styles));
+
+                let Some(closed) = closed else {
+                    return Err(if color.is_none() && styles.is_empty() {
+                        "The closing tag {/} doesn't match any opening specifier".to_string()
+                    } else {
+                        "A closing tag doesn't match any opening specifier with the same colors and styles".to_string()
+                    });
+                };
+
+                // the specifiers opened after the closed one can't be closed anymore, they stay placeholders
+                let opening = candidates[closed];
+                candidates.truncate(closed);
+
+                if let Specification { color, styles, .. } = parts[opening].clone() {
+                    parts[i] = Part::close(color.clone(), styles.clone());
+                    parts[opening] = Part::open(color, styles);
+                }
             }
+            _ => {}
         }
     }
 
-    Err("The specifiers are imbalanced: missing }".to_string())
+    Ok(parts)
 }
 
+fn closes(opening: &Part, color: &Colors, styles: &[Style]) -> bool {
+    match opening {
+        Specification { color: opening_color, styles: opening_styles, .. } => {
+            let same = |closing: Option<Color>, opening: Option<Color>| closing.is_none() || closing == opening;
+
+            same(color.foreground, opening_color.foreground)
+                && same(color.background, opening_color.background)
+                && same(color.underline, opening_color.underline)
+                && styles.iter().all(|it| opening_styles.contains(it))
+        }
+        _ => false,
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Copy, Clone)]
 enum ParserMode {
     IndexMode,
@@ -121,32 +233,60 @@ fn parse_spec(spec: &str) -> Result<Part, String> {
     let mut color = String::new();
     let mut style = String::new();
     let mut styles: Vec<String> = Vec::new();
+    let mut literal: Option<String> = None;
     let mut last_word = String::new();
+    // what comes before any `#`, `!`, `%` or `=`, that no mode takes
+    let mut stray = String::new();
+    // the color functions may contain any character between their parentheses
+    let mut depth = 0;
+    let mut chars = spec.chars();
 
-    for c in spec.chars() {
+    while let Some(c) = chars.next() {
         match c {
+            _ if depth > 0 || c == '(' => {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                last_word.push(c);
+                push_stray(mode, c, &mut stray);
+                push_char(mode, c, &mut text, &mut color, &mut style);
+            }
+            _ if mode.is_none() && opens_quote(c, &last_word) => {
+                if literal.is_some() {
+                    return Err(format!("The specifier '{}' has more than one quoted text", spec));
+                }
+                literal = Some(parse_quoted(&mut chars, c)?);
+            }
             '@' => {
+                refuse_stray(&stray, spec)?;
                 push_style(&mut style, &mut styles);
                 text.push('@');
                 mode = Some(AllArgsMode);
             }
             '#' => {
+                refuse_stray(&stray, spec)?;
                 push_style(&mut style, &mut styles);
                 mode = Some(ColorMode);
             }
             '%' => {
+                refuse_stray(&stray, spec)?;
                 push_style(&mut style, &mut styles);
                 mode = Some(IndexMode);
             }
             '!' => {
+                refuse_stray(&stray, spec)?;
                 push_style(&mut style, &mut styles);
                 mode = Some(StyleMode);
             }
             ' ' | '\t' => {
+                refuse_stray(&stray, spec)?;
                 last_word.clear();
                 mode = None;
             }
-            '=' =>
+            '=' => {
+                stray.clear();
                 match last_word.as_str() {
                     "color" => {
                         push_style(&mut style, &mut styles);
@@ -160,35 +300,45 @@ fn parse_spec(spec: &str) -> Result<Part, String> {
                         push_style(&mut style, &mut styles);
                         mode = Some(StyleMode)
                     }
-                    _ => panic!("Don't know how to interpret the keyword '{}' as a mode", last_word),
+                    "underline" | "font" => {
+                        push_style(&mut style, &mut styles);
+                        style.push_str(&last_word);
+                        style.push(':');
+                        mode = Some(StyleMode)
+                    }
+                    _ => return Err(format!("Don't know how to interpret the keyword '{}' as a mode", last_word)),
                 }
+            }
             _ => {
                 last_word.push(c);
-
-                match mode {
-                    Some(m) => match m {
-                        IndexMode => text.push(c),
-                        AllArgsMode => text.push(c),
-                        ColorMode => color.push(c),
-                        StyleMode => style.push(c),
-                    },
-                    None => { /* TODO this could be used to parse raw strings in the format */ }
-                }
+                push_stray(mode, c, &mut stray);
+                push_char(mode, c, &mut text, &mut color, &mut style);
             }
         }
     }
 
+    refuse_stray(&stray, spec)?;
     push_style(&mut style, &mut styles);
 
-    let color_spec: Option<Colors> = parse_color(&color.as_str());
-    let style_spec = parse_style(styles);
+    // preserving is not something that the terminal displays, it tells not to reset after the text
+    let (preserved, styles): (Vec<String>, Vec<String>) = styles.iter()
+        .flat_map(|it| it.split(',').map(|s| s.to_string()).collect::<Vec<String>>())
+        .partition(|it| matches!(it.trim().to_lowercase().as_str(), "preserve" | "keep" | "sticky"));
+
+    let color_spec = parse_color(color.as_str())?;
+    let style_spec = parse_style(styles)?;
 
     let trimmed = text.trim();
 
-    let text_spec: Text = if trimmed.is_empty() {
+    let text_spec: Text = if let Some(literal) = literal {
+        if !trimmed.is_empty() {
+            return Err(format!("The specifier '{}' has both a quoted text and an argument, expected only one of them", spec));
+        }
+        Text::Literal(literal)
+    } else if trimmed.is_empty() {
         Positional
     } else {
-        match trimmed.chars().nth(0) {
+        match trimmed.chars().next() {
             Some('@') => {
                 ALL_ARGS_REGEX.captures(text.trim()).and_then(|separator| {
                     let sep = separator.name("separator");
@@ -200,10 +350,8 @@ fn parse_spec(spec: &str) -> Result<Part, String> {
                     .as_str()
                     .trim()
                     .parse::<usize>()
-                    .and_then(|it| Ok(Indexed(it)))
-                    .unwrap_or_else(|it|
-                        panic!("Don't know how to interpret the text specification '{}'", text)
-                    )
+                    .map(Indexed)
+                    .map_err(|_| format!("Don't know how to interpret the text specification '{}'", text))?
             }
         }
     };
@@ -211,29 +359,128 @@ fn parse_spec(spec: &str) -> Result<Part, String> {
     Ok(
         Specification {
             text: text_spec,
-            color: color_spec.unwrap_or_else(|| Colors::none()),
+            color: color_spec,
             styles: style_spec,
+            preserve: !preserved.is_empty(),
         }
     )
 }
 
-fn push_style(mut style: &mut String, styles: &mut Vec<String>) {
+/// Reads a quoted literal up to its closing quote, with the same escape sequences as the format.
+fn parse_quoted(chars: &mut Chars, quote: char) -> Result<String, String> {
+    let mut literal = String::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) => match unescape(escaped) {
+                    Some(it) => literal.push(it),
+                    None => return Err(format!("Invalid escape sequence in a quoted text: \\{}", escaped)),
+                },
+                None => break,
+            },
+            _ if c == quote => return Ok(literal),
+            _ => literal.push(c),
+        }
+    }
+
+    Err(format!("The quoted text is missing its closing {}", quote))
+}
+
+fn push_char(mode: Option<ParserMode>, c: char, text: &mut String, color: &mut String, style: &mut String) {
+    if let Some(m) = mode {
+        match m {
+            IndexMode => text.push(c),
+            AllArgsMode => text.push(c),
+            ColorMode => color.push(c),
+            StyleMode => style.push(c),
+        }
+    }
+}
+
+fn push_stray(mode: Option<ParserMode>, c: char, stray: &mut String) {
+    if mode.is_none() {
+        stray.push(c);
+    }
+}
+
+fn refuse_stray(stray: &str, spec: &str) -> Result<(), String> {
+    if stray.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Don't know how to interpret '{}' in the specifier '{}'", stray, spec))
+    }
+}
+
+fn push_style(style: &mut String, styles: &mut Vec<String>) {
     if !style.is_empty() {
         styles.push(style.clone());
         style.clear();
     }
 }
 
-fn parse_color(so_far: &str) -> Option<Colors> {
-    COLOR_PARTS_REGEX.captures(so_far.trim()).map(|color| {
-        let foreground = color.name("fg").map(|s| { interpret_color(s) });
-        let background = color.name("bg").map(|s| { interpret_color(s) });
-        Colors { foreground, background }
-    })
+fn parse_color(so_far: &str) -> Result<Colors, String> {
+    let layers = split_top_level(so_far, '/');
+
+    let interpret_layer = |layer: Option<&&str>| layer
+        .map(|it| it.trim())
+        .filter(|it| !it.is_empty())
+        .map(interpret_color)
+        .transpose();
+
+    match layers.as_slice() {
+        [_, _, _, _, ..] => Err(format!("Too many colors in '{}', expected at most foreground/background/underline", so_far.trim())),
+        _ => {
+            // the translucent colors are blended over what's behind them, assuming a black terminal
+            let background = interpret_layer(layers.get(1))?
+                .map(|it| blend(it, Color::black()));
+            let foreground = interpret_layer(layers.first())?
+                .map(|it| blend(it, background.unwrap_or(Color::black())));
+            let underline = interpret_layer(layers.get(2))?
+                .map(|it| blend(it, background.unwrap_or(Color::black())));
+
+            Ok(Colors { foreground, background, underline })
+        }
+    }
+}
+
+fn blend((color, opacity): (Color, f64), behind: Color) -> Color {
+    if opacity < 1.0 {
+        color.mix(&behind, opacity)
+    } else {
+        color
+    }
 }
 
-fn interpret_color(s: Match) -> Color {
-    match s.as_str().trim() {
+/// Splits on the separators that are not inside parentheses.
+fn split_top_level(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ if c == separator && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+
+    parts
+}
+
+/// Interprets a color and its opacity.
+fn interpret_color(s: &str) -> Result<(Color, f64), String> {
+    let s = s.trim();
+    // the colors nested in functions may keep their #
+    let s = s.strip_prefix('#').unwrap_or(s);
+
+    Ok((match s {
         "0" | "k" | "black" => Color::black(),
         "1" | "r" | "red" => Color::red(),
         "2" | "g" | "green" => Color::green(),
@@ -252,64 +499,138 @@ fn interpret_color(s: Match) -> Color {
         "14" | "C" | "CYAN" => Color::bright_cyan(),
         "15" | "W" | "WHITE" => Color::bright_white(),
         more => {
-            let captures = HEX_COLOR.captures(&more);
-
-            if let Some(s) = captures.and_then(|it| it.name("code")) {
-                return match u32::from_str_radix(s.as_str(), 16) {
-                    Ok(value) => Color::u32_rgb(value),
-                    Err(e) => { panic!("{}", e.to_string()) }
+            if let Some(named) = named_color(more) {
+                return Ok((named, 1.0));
+            } else if let Some(s) = HEX_COLOR.captures(more).and_then(|it| it.name("code")) {
+                let code = if s.len() == 3 {
+                    // #rgb is a shorthand for #rrggbb
+                    s.as_str().chars().flat_map(|it| [it, it]).collect()
+                } else {
+                    s.as_str().to_string()
+                };
+                u32::from_str_radix(&code, 16)
+                    .map(Color::u32_rgb)
+                    .map_err(|e| e.to_string())?
+            } else if let Some(capture) = PALETTE_COLOR.captures(more).or_else(|| ANSI_COLOR.captures(more)) {
+                let index = capture.name("explicit").or_else(|| capture.name("index")).map_or("", |it| it.as_str());
+                match index.parse::<u8>() {
+                    Ok(value) => Color::palette(value),
+                    Err(_) => return Err(format!("The color index {} is out of range, it must be between 0 and 255", index)),
+                }
+            } else if let Some(capture) = GRAY_COLOR.captures(more) {
+                let level = &capture["level"];
+                match level.parse::<u8>() {
+                    Ok(value) if value < 24 => Color::gray(value),
+                    _ => return Err(format!("The gray level {} is out of range, it must be between 0 and 23", level)),
+                }
+            } else if let Some(capture) = FUNCTION_COLOR.captures(more) {
+                let name = capture["name"].to_lowercase();
+                return match name.as_str() {
+                    "lighten" | "darken" | "mix" | "complement" | "alpha" => color_modifier(&name, &capture["arguments"]),
+                    _ => color_function(&name, &capture["arguments"]),
                 };
-            } else if let Some(capture) = DEC_COLOR.captures(&more) {
-                let red: u8 = if let Some(red_str) = capture.name("red") {
-                    parse_as_u8(red_str.as_str())
-                } else { panic!("Can't find the red component.") };
-                let green: u8 = if let Some(green_str) = capture.name("green") {
-                    parse_as_u8(green_str.as_str())
-                } else { panic!("Can't find the green component.") };
-                let blue: u8 = if let Some(blue_str) = capture.name("blue") {
-                    parse_as_u8(blue_str.as_str())
-                } else { panic!("Can't find the blue component.") };
-
-                Color::rgb(red, green, blue)
             } else {
-                todo!("Don't know how to interpret the color '{}'", s.as_str())
+                return Err(format!("Don't know how to interpret the color '{}'", more));
             }
         }
+    }, 1.0))
+}
+
+/// Derives a color from other colors, like `lighten(red, 20%)` or `mix(#ff0000, #0000ff, 30%)`.
+fn color_modifier(name: &str, arguments: &str) -> Result<(Color, f64), String> {
+    let parameters = split_top_level(arguments, ',');
+
+    match (name, parameters.as_slice()) {
+        ("lighten", [color, amount]) => {
+            let (color, opacity) = interpret_color(color)?;
+            Ok((color.lighten(fraction("amount", amount)?), opacity))
+        }
+        ("darken", [color, amount]) => {
+            let (color, opacity) = interpret_color(color)?;
+            Ok((color.lighten(-fraction("amount", amount)?), opacity))
+        }
+        ("mix", [first, second]) => mix(first, second, 0.5),
+        ("mix", [first, second, weight]) => mix(first, second, fraction("weight", weight)?),
+        ("complement", [color]) => {
+            let (color, opacity) = interpret_color(color)?;
+            Ok((color.complement(), opacity))
+        }
+        ("alpha", [color, opacity]) => {
+            let (color, _) = interpret_color(color)?;
+            Ok((color, fraction("opacity", opacity)?))
+        }
+        _ => {
+            let usage = match name {
+                "lighten" => "lighten(color, amount)",
+                "darken" => "darken(color, amount)",
+                "mix" => "mix(color, color, weight)",
+                "complement" => "complement(color)",
+                _ => "alpha(color, opacity)",
+            };
+            Err(format!("Don't know how to interpret '{}({})', expected {}", name, arguments, usage))
+        }
     }
 }
 
-fn parse_as_u8(s: &str) -> u8 {
-    return match u8::from_str_radix(s, 10) {
-        Ok(value) => value,
-        Err(e) => { panic!("{}", e.to_string()) }
-    };
+fn mix(first: &str, second: &str, weight: f64) -> Result<(Color, f64), String> {
+    let (first, first_opacity) = interpret_color(first)?;
+    let (second, second_opacity) = interpret_color(second)?;
+    Ok((first.mix(&second, weight), first_opacity * weight + second_opacity * (1.0 - weight)))
 }
 
-fn parse_style(styles: Vec<String>) -> Vec<Style> {
+pub fn parse_style(styles: Vec<String>) -> Result<Vec<Style>, String> {
     let normalized: Vec<String> = styles.iter().flat_map(|s|
         s.split(',').map(|it| it.to_string()).collect::<Vec<String>>()
     ).collect();
 
-    normalized.iter().flat_map(|style|
+    let parsed: Result<Vec<Vec<Style>>, String> = normalized.iter().map(|style|
         match style.to_lowercase().as_str().trim() {
-            "strong" | "s" | "bold" => vec!(Strong),
-            "dim" | "d" | "faint" => vec!(Dim),
-            "italic" | "i" => vec!(Italic),
-            "underline" | "u" | "underscored" => vec!(Underline),
-            "blink" | "b" | "blinking" => vec!(Blink),
-            "reversed" | "r" | "reverse" | "invert" | "inverted" | "inverse" | "inversed" => vec!(Reversed),
-            "hidden" | "h" | "invisible" => vec!(Hidden),
-            "crossed-out" | "c" | "strikethrough" | "strike" => vec!(CrossedOut),
+            "reset" | "normal" | "plain" => Ok(vec!(Absent)),
+            "strong" | "s" | "bold" => Ok(vec!(Strong)),
+            "dim" | "d" | "faint" => Ok(vec!(Dim)),
+            "italic" | "i" => Ok(vec!(Italic)),
+            "underline" | "u" | "underscored" | "underline:single" => Ok(vec!(Underline)),
+            "double" | "double-underline" | "underline:double" => Ok(vec!(DoubleUnderline)),
+            "curly" | "curly-underline" | "squiggly" | "wavy" | "undercurl" | "underline:curly" => Ok(vec!(CurlyUnderline)),
+            "dotted" | "dotted-underline" | "underline:dotted" => Ok(vec!(DottedUnderline)),
+            "dashed" | "dashed-underline" | "underline:dashed" => Ok(vec!(DashedUnderline)),
+            "blink" | "b" | "blinking" => Ok(vec!(Blink)),
+            "reversed" | "r" | "reverse" | "invert" | "inverted" | "inverse" | "inversed" => Ok(vec!(Reversed)),
+            "hidden" | "h" | "invisible" => Ok(vec!(Hidden)),
+            "crossed-out" | "c" | "strikethrough" | "strike" => Ok(vec!(CrossedOut)),
+            "doubly-underlined" | "w" => Ok(vec!(DoublyUnderlined)),
+            "rapid-blink" | "q" | "fast-blink" | "quick" => Ok(vec!(RapidBlink)),
+            "framed" | "f" | "frame" => Ok(vec!(Framed)),
+            "encircled" | "e" | "circled" => Ok(vec!(Encircled)),
+            "overline" | "o" | "overlined" => Ok(vec!(Overline)),
+            "superscript" | "^" => Ok(vec!(Superscript)),
+            "subscript" | "_" => Ok(vec!(Subscript)),
+            "fraktur" | "gothic" | "font:fraktur" => Ok(vec!(Fraktur)),
+            "font:primary" => Ok(vec!(AlternativeFont(0))),
             glued => {
-                if glued.len() < 2 {
-                    panic!("Don't know how to interpret the style '{}'", style)
+                if let Some(kind) = glued.strip_prefix("underline:") {
+                    return Err(format!("Don't know the underline style '{}', expected single, double, curly, dotted or dashed", kind));
+                }
+
+                if let Some(font) = glued.strip_prefix("font:").or_else(|| glued.strip_prefix("font")) {
+                    return match font.parse::<u8>() {
+                        Ok(value) if value <= 9 => Ok(vec!(AlternativeFont(value))),
+                        _ => Err(format!("Don't know the font '{}', expected 0 to 9, primary or fraktur", font)),
+                    };
+                }
+
+                if glued.chars().count() < 2 {
+                    return Err(format!("Don't know how to interpret the style '{}'", style));
                 }
 
                 // try to interpret it as individual chars
                 parse_style(glued.chars().map(|it| it.to_string()).collect())
+                    .map_err(|_| format!("Don't know how to interpret the style '{}'", style))
             }
         }
-    ).collect()
+    ).collect();
+
+    parsed.map(|it| it.into_iter().flatten().collect())
 }
 
 
@@ -317,20 +638,15 @@ fn parse_style(styles: Vec<String>) -> Vec<Style> {
 mod tests {
     use crate::model::{Color, Colors, Part};
     use crate::model::Part::{Literal, Specification};
-    use crate::model::Style::{Blink, Strong, Dim, Hidden, Reversed, Italic, CrossedOut, Underline};
+    use crate::model::Style::{Absent, AlternativeFont, Blink, Strong, Dim, Hidden, Reversed, Italic, CrossedOut, Underline, DoubleUnderline, CurlyUnderline, DottedUnderline, DashedUnderline, DoublyUnderlined, RapidBlink, Fraktur, Framed, Encircled, Overline, Superscript, Subscript};
+    use crate::model::Text;
     use crate::model::Text::Positional;
     use crate::parser::{parse_color, parse_format, parse_spec};
 
-    // TODO detect invalid cases:
-    //  {garbage value}
-
-    // TODO refuse to mix positional, indexed and named, only 1 of each
-
-    // TODO: test case when the style can't be parsed, like "baz"
-    //  and output an error message explaining why it's invalid
+    // TODO refuse to mix the named specifiers with the others, once there are some
 
     fn test_ok_format(format: &str, parts: Vec<Part>) {
-        let specs = parse_format(&format.to_string());
+        let specs = parse_format(format);
         let ok = specs.ok().unwrap();
         assert_eq!(ok.len(), parts.len());
 
@@ -341,7 +657,7 @@ mod tests {
 
     #[test]
     fn parse_a_string_that_contains_no_spec_in_default_mode() {
-        let specs = parse_format(&"Hello, format!".to_string());
+        let specs = parse_format("Hello, format!");
         let ok = specs.ok().unwrap();
         assert_eq!(ok.len(), 1);
         assert_eq!(ok[0], Literal("Hello, format!".to_string()));
@@ -349,7 +665,7 @@ mod tests {
 
     #[test]
     fn parse_a_string_that_contains_no_spec_but_special_chars_in_default_mode() {
-        let specs = parse_format(&r#"Look at those dirty chars: \{ \\ \}"#.to_string());
+        let specs = parse_format(r#"Look at those dirty chars: \{ \\ \}"#);
         let ok = specs.ok().unwrap();
         assert_eq!(ok.len(), 1);
         assert_eq!(ok[0], Literal(r#"Look at those dirty chars: { \ }"#.to_string()));
@@ -357,7 +673,7 @@ mod tests {
 
     #[test]
     fn parse_a_string_that_contains_1_spec_in_default_mode() {
-        let specs = parse_format(&"Spec={}".to_string());
+        let specs = parse_format("Spec={}");
         let ok = specs.ok().unwrap();
         assert_eq!(ok.len(), 2);
         assert_eq!(ok[0], Literal("Spec=".to_string()));
@@ -366,7 +682,7 @@ mod tests {
 
     #[test]
     fn parse_a_nested_format() {
-        let specs = parse_format(&"Whatever {{}".to_string());
+        let specs = parse_format("Whatever {{}");
         let err = specs.err().unwrap();
         // TODO: improvement: tell the char that caused the issue
         assert_eq!(err, "Can't nest specifiers".to_string());
@@ -374,14 +690,14 @@ mod tests {
 
     #[test]
     fn parse_an_imbalanced_format() {
-        let specs = parse_format(&"Imbalanced {".to_string());
+        let specs = parse_format("Imbalanced {");
         let err = specs.err().unwrap();
         assert_eq!(err, "The specifiers are imbalanced: missing }".to_string());
     }
 
     #[test]
     fn a_single_empty_string_as_the_single_argument_is_valid_and_does_nothing() {
-        let specs = parse_format(&"".to_string());
+        let specs = parse_format("");
         let ok = specs.ok().unwrap();
         assert_eq!(ok.len(), 0);
     }
@@ -492,13 +808,13 @@ mod tests {
     // color specifiers
 
     fn test_color_spec(spec: &str, color: Color) {
-        let specs = parse_color(&spec.to_string());
+        let specs = parse_color(spec);
         let ok = specs.unwrap();
         assert_eq!(ok, Colors::new_fg(color));
     }
 
     fn test_background_color_spec(spec: &str, color: Color) {
-        let specs = parse_color(&spec.to_string());
+        let specs = parse_color(spec);
         let ok = specs.unwrap();
         assert_eq!(ok, Colors::new_bg(color));
     }
@@ -602,6 +918,215 @@ mod tests {
         test_color_spec("rgb(84,55,15)", Color::rgb(0x54, 0x37, 0x0f));
     }
 
+    #[test]
+    fn parse_short_hex_colors() {
+        test_color_spec("fc0", Color::rgb(0xff, 0xcc, 0x00));
+        test_color_spec("ABC", Color::rgb(0xaa, 0xbb, 0xcc));
+    }
+
+    #[test]
+    fn three_characters_are_short_hex_colors_rather_than_palette_indexes() {
+        test_color_spec("c00", Color::rgb(0xcc, 0x00, 0x00));
+        test_color_spec("123", Color::rgb(0x11, 0x22, 0x33));
+        test_color_spec("999", Color::rgb(0x99, 0x99, 0x99));
+        test_color_spec("c0f", Color::rgb(0xcc, 0x00, 0xff));
+        test_color_spec("99", Color::palette(99));
+        test_color_spec("c123", Color::palette(123));
+        test_color_spec("c008", Color::palette(8));
+    }
+
+    #[test]
+    fn parse_css_rgb_function() {
+        test_color_spec("rgb(84, 55, 15)", Color::rgb(84, 55, 15));
+        test_color_spec("rgb(84 55 15)", Color::rgb(84, 55, 15));
+        test_color_spec("rgb(100% 50% 0%)", Color::rgb(255, 128, 0));
+        test_color_spec("RGB(1,2,3)", Color::rgb(1, 2, 3));
+    }
+
+    #[test]
+    fn parse_css_hsl_function() {
+        test_color_spec("hsl(0 100% 50%)", Color::rgb(255, 0, 0));
+        test_color_spec("hsl(120, 100%, 25%)", Color::rgb(0, 128, 0));
+        test_color_spec("hsl(0.5turn 100% 50%)", Color::rgb(0, 255, 255));
+    }
+
+    #[test]
+    fn parse_css_hwb_function() {
+        test_color_spec("hwb(0 0% 0%)", Color::rgb(255, 0, 0));
+        test_color_spec("hwb(60 0% 50%)", Color::rgb(128, 128, 0));
+        test_color_spec("hwb(0 60% 60%)", Color::rgb(128, 128, 128));
+    }
+
+    #[test]
+    fn parse_css_lab_functions() {
+        test_color_spec("lab(100 0 0)", Color::rgb(255, 255, 255));
+        test_color_spec("lab(50% 0 0)", Color::rgb(119, 119, 119));
+        test_color_spec("lch(0 0 0)", Color::rgb(0, 0, 0));
+        test_color_spec("oklab(1 0 0)", Color::rgb(255, 255, 255));
+        test_color_spec("oklch(62.8% 0.2577 29.23)", Color::rgb(255, 0, 0));
+    }
+
+    #[test]
+    fn reject_out_of_range_css_functions() {
+        assert_eq!(
+            parse_color("rgb(300, 0, 0)").err(),
+            Some("The red component 300 is out of range, it must be between 0 and 255".to_string())
+        );
+        assert_eq!(
+            parse_color("hsl(0 120% 50%)").err(),
+            Some("The saturation 120% is out of range, it must be between 0% and 100%".to_string())
+        );
+        assert_eq!(
+            parse_color("oklch(2 0.1 20)").err(),
+            Some("The lightness 2 is out of range, it must be between 0 and 1".to_string())
+        );
+        assert_eq!(
+            parse_color("rgb(1 2)").err(),
+            Some("The function 'rgb' takes 3 components but got 2".to_string())
+        );
+    }
+
+    #[test]
+    fn lighten_and_darken_colors() {
+        test_color_spec("lighten(#000000, 20%)", Color::rgb(51, 51, 51));
+        test_color_spec("darken(#ffffff, 0.5)", Color::rgb(128, 128, 128));
+        test_color_spec("darken(black, 10%)", Color::rgb(0, 0, 0));
+    }
+
+    #[test]
+    fn mix_colors() {
+        test_color_spec("mix(#ff0000, #0000ff)", Color::rgb(128, 0, 128));
+        test_color_spec("mix(#ff0000, #0000ff, 100%)", Color::rgb(255, 0, 0));
+        test_color_spec("mix(lighten(#000, 20%), #fff, 0)", Color::rgb(255, 255, 255));
+    }
+
+    #[test]
+    fn complement_colors() {
+        test_color_spec("complement(#ff0000)", Color::rgb(0, 255, 255));
+        test_color_spec("complement(teal)", Color::rgb(128, 0, 0));
+    }
+
+    #[test]
+    fn blend_translucent_colors_over_the_background() {
+        let specs = parse_color("alpha(#ffcc00, 0.5)/black");
+        assert_eq!(specs.unwrap(), Colors::new(Color::rgb(128, 102, 0), Color::black()));
+
+        let specs = parse_color("alpha(#000000, 25%)/#ffffff");
+        assert_eq!(specs.unwrap(), Colors::new(Color::rgb(191, 191, 191), Color::rgb(255, 255, 255)));
+    }
+
+    #[test]
+    fn blend_translucent_colors_over_black_when_there_is_no_background() {
+        test_color_spec("rgb(255 0 0 / 50%)", Color::rgb(128, 0, 0));
+        test_color_spec("rgba(255, 0, 0, 0.5)", Color::rgb(128, 0, 0));
+    }
+
+    #[test]
+    fn color_modifiers_can_be_used_in_specifiers() {
+        parse_ok_spec(
+            "#mix(#ff0000, #0000ff, 50%) %1",
+            Part::indexed_color(1, Colors::new_fg(Color::rgb(128, 0, 128))),
+        );
+    }
+
+    #[test]
+    fn reject_invalid_color_modifiers() {
+        assert_eq!(
+            parse_color("lighten(red)").err(),
+            Some("Don't know how to interpret 'lighten(red)', expected lighten(color, amount)".to_string())
+        );
+        assert_eq!(
+            parse_color("mix(red, blue, 130%)").err(),
+            Some("The weight 130% is out of range, it must be between 0% and 100%".to_string())
+        );
+    }
+
+    #[test]
+    fn color_functions_may_contain_spaces_and_specifier_symbols() {
+        parse_ok_spec(
+            "#hsl(120deg 100% 25%)/rgb(0, 0, 100%) %1",
+            Part::indexed_color(1, Colors::new(Color::rgb(0, 128, 0), Color::rgb(0, 0, 255))),
+        );
+    }
+
+    #[test]
+    fn parse_256_colors_palette_specs() {
+        test_color_spec("16", Color::palette(16));
+        test_color_spec("c208", Color::palette(208));
+        test_color_spec("c255", Color::palette(255));
+        test_color_spec("ansi(208)", Color::palette(208));
+        test_color_spec("ansi( 9 )", Color::bright_red());
+    }
+
+    #[test]
+    fn parse_grayscale_ramp_specs() {
+        test_color_spec("gray0", Color::palette(232));
+        test_color_spec("gray23", Color::palette(255));
+        test_color_spec("grey12", Color::gray(12));
+    }
+
+    #[test]
+    fn reject_out_of_range_palette_colors() {
+        assert_eq!(
+            parse_color("c256").err(),
+            Some("The color index 256 is out of range, it must be between 0 and 255".to_string())
+        );
+        assert_eq!(
+            parse_color("gray24").err(),
+            Some("The gray level 24 is out of range, it must be between 0 and 23".to_string())
+        );
+    }
+
+    #[test]
+    fn reject_unknown_colors() {
+        assert_eq!(
+            parse_spec("#foo").err(),
+            Some("Don't know how to interpret the color 'foo'".to_string())
+        );
+    }
+
+    #[test]
+    fn reject_what_the_specifier_cant_interpret() {
+        assert_eq!(
+            parse_spec("foo bar").err(),
+            Some("Don't know how to interpret 'foo' in the specifier 'foo bar'".to_string())
+        );
+        assert_eq!(
+            parse_spec("#r ok").err(),
+            Some("Don't know how to interpret 'ok' in the specifier '#r ok'".to_string())
+        );
+        assert_eq!(
+            parse_spec("x=1").err(),
+            Some("Don't know how to interpret the keyword 'x' as a mode".to_string())
+        );
+        assert_eq!(
+            parse_spec("%x").err(),
+            Some("Don't know how to interpret the text specification 'x'".to_string())
+        );
+        assert_eq!(parse_format("a\\q").err(), Some("Invalid escape sequence: \\q".to_string()));
+    }
+
+    #[test]
+    fn parse_css_named_colors() {
+        test_color_spec("orange", Color::rgb(0xff, 0xa5, 0x00));
+        test_color_spec("teal", Color::rgb(0x00, 0x80, 0x80));
+        test_color_spec("rebeccapurple", Color::rgb(0x66, 0x33, 0x99));
+        test_color_spec("brown", Color::rgb(0xa5, 0x2a, 0x2a));
+    }
+
+    #[test]
+    fn css_named_colors_are_case_insensitive() {
+        test_color_spec("RebeccaPurple", Color::rgb(0x66, 0x33, 0x99));
+        test_color_spec("SALMON", Color::rgb(0xfa, 0x80, 0x72));
+    }
+
+    #[test]
+    fn css_names_dont_override_the_ansi_colors() {
+        test_color_spec("green", Color::green());
+        test_color_spec("GREEN", Color::bright_green());
+        assert!(parse_color("Green").is_err());
+    }
+
     #[test]
     fn parse_background_specs() {
         test_background_color_spec("/k", Color::black());
@@ -617,7 +1142,7 @@ mod tests {
     // escape sequences
 
     fn check_backslash_notation(notation: &str, code: &str) {
-        let specs = parse_format(&notation.to_string());
+        let specs = parse_format(notation);
         let ok = &specs.unwrap()[0];
         assert_eq!(ok, &Part::literal(code));
     }
@@ -629,37 +1154,37 @@ mod tests {
 
     #[test]
     fn interpret_backslash_b_as_backspace() {
-        check_backslash_notation(&r#"\b"#.to_string(), "\x08");
+        check_backslash_notation(r#"\b"#, "\x08");
     }
 
     #[test]
     fn interpret_backslash_t_as_horizontal_tab() {
-        check_backslash_notation(&r#"\t"#.to_string(), "\x09");
+        check_backslash_notation(r#"\t"#, "\x09");
     }
 
     #[test]
     fn interpret_backslash_n_as_line_feed() {
-        check_backslash_notation(&r#"\n"#.to_string(), "\x0a");
+        check_backslash_notation(r#"\n"#, "\x0a");
     }
 
     #[test]
     fn interpret_backslash_v_as_vertical_tab() {
-        check_backslash_notation(&r#"\v"#.to_string(), "\x0b");
+        check_backslash_notation(r#"\v"#, "\x0b");
     }
 
     #[test]
     fn interpret_backslash_f_as_form_feed() {
-        check_backslash_notation(&r#"\f"#.to_string(), "\x0c");
+        check_backslash_notation(r#"\f"#, "\x0c");
     }
 
     #[test]
     fn interpret_backslash_r_as_carriage_return() {
-        check_backslash_notation(&r#"\r"#.to_string(), "\x0d");
+        check_backslash_notation(r#"\r"#, "\x0d");
     }
 
     #[test]
     fn interpret_backslash_e_as_escape() {
-        check_backslash_notation(&r#"\e"#.to_string(), "\x1b");
+        check_backslash_notation(r#"\e"#, "\x1b");
     }
 
     // font style
@@ -706,6 +1231,68 @@ mod tests {
         parse_ok_spec("!underscored", Part::positional_style(Underline));
     }
 
+    #[test]
+    fn parse_extended_underline_styles() {
+        parse_ok_spec("!double", Part::positional_style(DoubleUnderline));
+        parse_ok_spec("!curly", Part::positional_style(CurlyUnderline));
+        parse_ok_spec("!squiggly", Part::positional_style(CurlyUnderline));
+        parse_ok_spec("!dotted", Part::positional_style(DottedUnderline));
+        parse_ok_spec("!dashed-underline", Part::positional_style(DashedUnderline));
+
+        parse_ok_spec("underline=single", Part::positional_style(Underline));
+        parse_ok_spec("underline=double", Part::positional_style(DoubleUnderline));
+        parse_ok_spec("underline=curly", Part::positional_style(CurlyUnderline));
+        parse_ok_spec("underline=dotted", Part::positional_style(DottedUnderline));
+        parse_ok_spec("underline=dashed", Part::positional_style(DashedUnderline));
+    }
+
+    #[test]
+    fn reject_unknown_underline_styles() {
+        assert_eq!(
+            parse_spec("underline=zigzag").err(),
+            Some("Don't know the underline style 'zigzag', expected single, double, curly, dotted or dashed".to_string())
+        );
+    }
+
+    #[test]
+    fn reject_unknown_styles() {
+        assert_eq!(
+            parse_spec("!baz").err(),
+            Some("Don't know how to interpret the style 'baz'".to_string())
+        );
+        assert_eq!(
+            parse_spec("!x").err(),
+            Some("Don't know how to interpret the style 'x'".to_string())
+        );
+    }
+
+    #[test]
+    fn the_third_color_is_the_underline_color() {
+        let specs = parse_color("red/black/yellow");
+        assert_eq!(
+            specs.unwrap(),
+            Colors { foreground: Some(Color::red()), background: Some(Color::black()), underline: Some(Color::yellow()) }
+        );
+
+        parse_ok_spec(
+            "!curly #//ff0000",
+            Specification {
+                text: Positional,
+                color: Colors::new_underline(Color::rgb(255, 0, 0)),
+                styles: vec!(CurlyUnderline),
+                preserve: false,
+            },
+        );
+    }
+
+    #[test]
+    fn reject_more_than_3_colors() {
+        assert_eq!(
+            parse_color("r/g/b/y").err(),
+            Some("Too many colors in 'r/g/b/y', expected at most foreground/background/underline".to_string())
+        );
+    }
+
     #[test]
     fn parse_blink_style() {
         parse_ok_spec("style=blink", Part::positional_style(Blink));
@@ -760,6 +1347,48 @@ mod tests {
         parse_ok_spec("!strike", Part::positional_style(CrossedOut));
     }
 
+    #[test]
+    fn parse_the_less_common_styles() {
+        parse_ok_spec("!overline", Part::positional_style(Overline));
+        parse_ok_spec("!o", Part::positional_style(Overline));
+        parse_ok_spec("!doubly-underlined", Part::positional_style(DoublyUnderlined));
+        parse_ok_spec("!w", Part::positional_style(DoublyUnderlined));
+        parse_ok_spec("!rapid-blink", Part::positional_style(RapidBlink));
+        parse_ok_spec("!q", Part::positional_style(RapidBlink));
+        parse_ok_spec("!framed", Part::positional_style(Framed));
+        parse_ok_spec("!f", Part::positional_style(Framed));
+        parse_ok_spec("!encircled", Part::positional_style(Encircled));
+        parse_ok_spec("!e", Part::positional_style(Encircled));
+        parse_ok_spec("!superscript", Part::positional_style(Superscript));
+        parse_ok_spec("!^", Part::positional_style(Superscript));
+        parse_ok_spec("!subscript", Part::positional_style(Subscript));
+        parse_ok_spec("!_", Part::positional_style(Subscript));
+    }
+
+    #[test]
+    fn parse_the_alternative_fonts() {
+        parse_ok_spec("!font3", Part::positional_style(AlternativeFont(3)));
+        parse_ok_spec("font=9", Part::positional_style(AlternativeFont(9)));
+        parse_ok_spec("font=fraktur", Part::positional_style(Fraktur));
+        parse_ok_spec("!gothic", Part::positional_style(Fraktur));
+        parse_ok_spec("font=primary", Part::positional_style(AlternativeFont(0)));
+        parse_ok_spec("!font0", Part::positional_style(AlternativeFont(0)));
+        assert_eq!(
+            parse_spec("font=10").err(),
+            Some("Don't know the font '10', expected 0 to 9, primary or fraktur".to_string())
+        );
+    }
+
+    #[test]
+    fn sub_is_still_strong_underline_and_blink_glued() {
+        parse_ok_spec("!sub", Part::positional_styles(vec!(Strong, Underline, Blink)));
+    }
+
+    #[test]
+    fn the_new_short_names_can_be_glued_to_the_old_ones() {
+        parse_ok_spec("!so^", Part::positional_styles(vec!(Strong, Overline, Superscript)));
+    }
+
     #[test]
     fn style_overload() {
         parse_ok_spec(
@@ -768,6 +1397,24 @@ mod tests {
         )
     }
 
+    #[test]
+    fn preserve_is_a_flag_rather_than_a_style() {
+        parse_ok_spec(
+            "#red!bold,sticky",
+            Specification { text: Positional, color: Colors::new_fg(Color::red()), styles: vec!(Strong), preserve: true },
+        );
+        parse_ok_spec(
+            "!preserve",
+            Specification { text: Positional, color: Colors::none(), styles: vec!(), preserve: true },
+        );
+    }
+
+    #[test]
+    fn parse_the_reset_style() {
+        parse_ok_spec("!reset", Part::positional_style(Absent));
+        parse_ok_spec("!normal", Part::positional_style(Absent));
+    }
+
     #[test]
     fn style_overload_with_colors() {
         parse_ok_spec(
@@ -776,6 +1423,7 @@ mod tests {
                 text: Positional,
                 color: Colors::new(Color::red(), Color::blue()),
                 styles: vec!(Italic, Strong, Dim, Blink, CrossedOut, Hidden, Underline),
+                preserve: false,
             },
         )
     }
@@ -835,6 +1483,109 @@ mod tests {
         )
     }
 
+    #[test]
+    fn parse_quoted_literals() {
+        test_ok_format(
+            r#"{"WARNING" #Y!s}: {'done' #g}"#,
+            vec!(
+                Specification { text: Text::Literal("WARNING".to_string()), color: Colors::new_fg(Color::bright_yellow()), styles: vec!(Strong), preserve: false },
+                Part::literal(": "),
+                Specification { text: Text::Literal("done".to_string()), color: Colors::new_fg(Color::green()), styles: vec!(), preserve: false },
+            ),
+        )
+    }
+
+    #[test]
+    fn quoted_literals_may_contain_braces_quotes_and_escapes() {
+        test_ok_format(
+            r#"{"{\"a\"}\t'b'" #r}"#,
+            vec!(
+                Specification { text: Text::Literal("{\"a\"}\t'b'".to_string()), color: Colors::new_fg(Color::red()), styles: vec!(), preserve: false },
+            ),
+        )
+    }
+
+    #[test]
+    fn a_quote_inside_a_word_is_not_a_literal() {
+        test_ok_format("{@|'|}", vec!(Specification { text: Text::AllArgs("'".to_string()), color: Colors::none(), styles: vec!(), preserve: false }))
+    }
+
+    #[test]
+    fn reject_invalid_quoted_literals() {
+        assert_eq!(parse_format(r#"{"open #r}"#).err(), Some("The quoted text is missing its closing \"".to_string()));
+        assert_eq!(
+            parse_format(r#"{"a" %1}"#).err(),
+            Some(r#"The specifier '"a" %1' has both a quoted text and an argument, expected only one of them"#.to_string())
+        );
+        assert_eq!(
+            parse_format(r#"{"a" 'b'}"#).err(),
+            Some(r#"The specifier '"a" 'b'' has more than one quoted text"#.to_string())
+        );
+        assert_eq!(
+            parse_format(r#"{"\q"}"#).err(),
+            Some(r#"Invalid escape sequence in a quoted text: \q"#.to_string())
+        );
+    }
+
+    #[test]
+    fn closing_tags_turn_specifiers_into_scopes() {
+        test_ok_format(
+            "{#red}error: {!bold}{%1}{/!bold} happened{/}",
+            vec!(
+                Part::open(Colors::new_fg(Color::red()), vec!()),
+                Part::literal("error: "),
+                Part::open(Colors::none(), vec!(Strong)),
+                Part::indexed(1),
+                Part::close(Colors::none(), vec!(Strong)),
+                Part::literal(" happened"),
+                Part::close(Colors::new_fg(Color::red()), vec!()),
+            ),
+        )
+    }
+
+    #[test]
+    fn a_closing_tag_closes_the_innermost_matching_scope() {
+        test_ok_format(
+            "{#red}a{#blue}b{/#red}",
+            vec!(
+                Part::open(Colors::new_fg(Color::red()), vec!()),
+                Part::literal("a"),
+                Part::positional_color(Color::blue()),
+                Part::literal("b"),
+                Part::close(Colors::new_fg(Color::red()), vec!()),
+            ),
+        )
+    }
+
+    #[test]
+    fn specifiers_without_closing_tags_stay_placeholders() {
+        test_ok_format(
+            "{#red}{#blue}x{/}",
+            vec!(
+                Part::positional_color(Color::red()),
+                Part::open(Colors::new_fg(Color::blue()), vec!()),
+                Part::literal("x"),
+                Part::close(Colors::new_fg(Color::blue()), vec!()),
+            ),
+        )
+    }
+
+    #[test]
+    fn reject_unbalanced_closing_tags() {
+        assert_eq!(
+            parse_format("text{/}").err(),
+            Some("The closing tag {/} doesn't match any opening specifier".to_string())
+        );
+        assert_eq!(
+            parse_format("{#red}text{/!bold}").err(),
+            Some("A closing tag doesn't match any opening specifier with the same colors and styles".to_string())
+        );
+        assert_eq!(
+            parse_format("{#red}text{/%1}").err(),
+            Some("A closing tag can only contain colors and styles: '{/%1}'".to_string())
+        );
+    }
+
     #[test]
     fn whitespace_inside_specifier_is_ignored() {
         test_ok_format(
@@ -0,0 +1,127 @@
+use crate::html::css_color;
+use crate::model::{Appearance, Color, Style};
+use crate::span::Spans;
+
+/// What polybar's format tags can display.
+#[derive(PartialEq, Debug, Default, Clone)]
+struct Tags {
+    foreground: Option<Color>,
+    background: Option<Color>,
+    line_color: Option<Color>,
+    underline: bool,
+    overline: bool,
+    /// The index of the font in polybar's configuration, starting at 1
+    font: Option<u8>,
+}
+
+impl Tags {
+    fn of(appearance: &Appearance) -> Self {
+        let styles = &appearance.styles;
+
+        Tags {
+            foreground: appearance.colors.foreground,
+            background: appearance.colors.background,
+            line_color: appearance.colors.underline,
+            underline: styles.iter().any(Style::is_underline),
+            overline: styles.contains(&Style::Overline),
+            // font1 is the first alternative font, after polybar's main font
+            font: styles.iter().find_map(|it| match it {
+                Style::AlternativeFont(font) => Some(font + 1),
+                _ => None,
+            }),
+        }
+    }
+}
+
+/// Outputs the text with polybar's format tags, like `%{F#cd0000}`.
+///
+/// Polybar has no bold, italic, or other style than the lines and the fonts, the other styles are left out.
+pub fn render_polybar(spans: &Spans) -> String {
+    let mut result = String::new();
+    let mut current = Tags::default();
+
+    for span in spans.spans.iter().filter(|it| !it.text.is_empty()) {
+        let next = Tags::of(&span.appearance);
+        result.push_str(&transition(&current, &next));
+        result.push_str(&span.text.replace('%', "%%"));
+        current = next;
+    }
+
+    result.push_str(&transition(&current, &Tags::default()));
+
+    result
+}
+
+fn transition(from: &Tags, to: &Tags) -> String {
+    let mut tags = String::new();
+
+    let colors = [
+        (from.foreground, to.foreground, 'F'),
+        (from.background, to.background, 'B'),
+        (from.line_color, to.line_color, 'u'),
+    ];
+
+    for (before, after, tag) in colors {
+        if before != after {
+            match after {
+                Some(color) => tags.push_str(&format!("%{{{}{}}}", tag, css_color(color))),
+                None => tags.push_str(&format!("%{{{}-}}", tag)),
+            }
+        }
+    }
+
+    for (before, after, line) in [(from.underline, to.underline, 'u'), (from.overline, to.overline, 'o')] {
+        if before != after {
+            tags.push_str(&format!("%{{{}{}}}", if after { '+' } else { '-' }, line));
+        }
+    }
+
+    if from.font != to.font {
+        match to.font {
+            Some(font) => tags.push_str(&format!("%{{T{}}}", font)),
+            None => tags.push_str("%{T-}"),
+        }
+    }
+
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Appearance, Color, Colors};
+    use crate::model::Style::{AlternativeFont, Strong, Underline};
+    use crate::polybar::render_polybar;
+    use crate::span::spans_of;
+
+    fn polybar(spans: Vec<(&str, Appearance)>) -> String {
+        render_polybar(&spans_of(spans))
+    }
+
+    #[test]
+    fn output_the_color_tags_and_restore_them() {
+        let red_on_black = Appearance::plain().with(&Colors::new(Color::red(), Color::black()), &[Strong]);
+
+        assert_eq!(
+            polybar(vec!(("CPU", red_on_black), (" 99%", Appearance::plain()))),
+            "%{F#cd0000}%{B#000000}CPU%{F-}%{B-} 99%%"
+        );
+    }
+
+    #[test]
+    fn only_output_the_tags_that_change() {
+        let red = Appearance::plain().with(&Colors::new_fg(Color::red()), &[]);
+        let red_underlined = red.with(&Colors::new_underline(Color::blue()), &[Underline]);
+
+        assert_eq!(
+            polybar(vec!(("a", red.clone()), ("b", red_underlined), ("c", red))),
+            "%{F#cd0000}a%{u#0000ee}%{+u}b%{u-}%{-u}c%{F-}"
+        );
+    }
+
+    #[test]
+    fn use_the_alternative_fonts() {
+        let font = Appearance::plain().with(&Colors::none(), &[AlternativeFont(1)]);
+
+        assert_eq!(polybar(vec!(("icon", font))), "%{T2}icon%{T-}");
+    }
+}
@@ -0,0 +1,176 @@
+use crate::config::Shell;
+use crate::html::css_color;
+use crate::model::{Appearance, Color, Colors, Style};
+use crate::span::Spans;
+use crate::writer::{diff_codes, sgr_transition};
+
+/// Outputs the text for a shell prompt, where the line editor must know which characters take no space.
+pub fn render_prompt(spans: &Spans, shell: Shell) -> String {
+    let mut result = String::new();
+    let mut displayed = Appearance::plain();
+
+    for span in spans.spans.iter().filter(|it| !it.text.is_empty()) {
+        result.push_str(&transition(&displayed, &span.appearance, shell));
+        result.push_str(&escape(&span.text, shell));
+        displayed = span.appearance.clone();
+    }
+
+    result.push_str(&transition(&displayed, &spans.end, shell));
+
+    result
+}
+
+fn transition(from: &Appearance, to: &Appearance, shell: Shell) -> String {
+    match shell {
+        // readline's markers, because bash doesn't interpret \[ and \] in the output of a command
+        Shell::Bash => wrap(sgr_transition(from, to), "\x01", "\x02"),
+        Shell::Zsh => wrap(sgr_transition(from, to), "%{", "%}"),
+        Shell::ZshNative => zsh_transition(from, to),
+        // fish finds the escape sequences by itself
+        Shell::Fish => sgr_transition(from, to),
+    }
+}
+
+fn wrap(sequence: String, start: &str, end: &str) -> String {
+    if sequence.is_empty() {
+        sequence
+    } else {
+        format!("{}{}{}", start, sequence, end)
+    }
+}
+
+fn escape(text: &str, shell: Shell) -> String {
+    match shell {
+        Shell::Zsh | Shell::ZshNative => text.replace('%', "%%"),
+        Shell::Bash | Shell::Fish => text.to_string(),
+    }
+}
+
+/// Uses zsh's prompt sequences, like `%F{red}` or `%B`, for what they can display and escape sequences for the rest.
+fn zsh_transition(from: &Appearance, to: &Appearance) -> String {
+    let mut result = String::new();
+
+    let codes = diff_codes(&non_native(from), &non_native(to));
+    if !codes.is_empty() {
+        result.push_str(&format!("%{{\x1b[{}m%}}", codes.join(";")));
+    }
+
+    // dim shares its off code with bold, turning dim off turns bold off too
+    let bold_was_reset = codes.iter().any(|it| it == "22");
+
+    let flags = [
+        (from.styles.contains(&Style::Strong) && !bold_was_reset, to.styles.contains(&Style::Strong), "%B", "%b"),
+        (is_underlined(from), is_underlined(to), "%U", "%u"),
+        (from.styles.contains(&Style::Reversed), to.styles.contains(&Style::Reversed), "%S", "%s"),
+    ];
+
+    for (before, after, on, off) in flags {
+        if before != after {
+            result.push_str(if after { on } else { off });
+        }
+    }
+
+    let colors = [
+        (from.colors.foreground, to.colors.foreground, 'F', 'f'),
+        (from.colors.background, to.colors.background, 'K', 'k'),
+    ];
+
+    for (before, after, set, reset) in colors {
+        if before != after {
+            match after {
+                Some(color) => result.push_str(&format!("%{}{{{}}}", set, zsh_color(color))),
+                None => result.push_str(&format!("%{}", reset)),
+            }
+        }
+    }
+
+    result
+}
+
+fn zsh_color(color: Color) -> String {
+    match color {
+        Color::Byte(index) => index.to_string(),
+        Color::RGB { .. } => css_color(color),
+    }
+}
+
+fn is_underlined(appearance: &Appearance) -> bool {
+    appearance.styles.iter().any(Style::is_underline)
+}
+
+/// What zsh can't display with its own prompt sequences.
+fn non_native(appearance: &Appearance) -> Appearance {
+    Appearance {
+        colors: Colors { foreground: None, background: None, underline: appearance.colors.underline },
+        styles: appearance.styles.iter()
+            .filter(|it| !matches!(it, Style::Strong | Style::Reversed) && !it.is_underline())
+            .copied()
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Shell;
+    use crate::model::{Appearance, Color, Colors, Style};
+    use crate::model::Style::{Dim, Italic, Strong, Underline};
+    use crate::prompt::render_prompt;
+    use crate::span::spans_of;
+
+    fn prompt(spans: Vec<(&str, Appearance)>, shell: Shell) -> String {
+        render_prompt(&spans_of(spans), shell)
+    }
+
+    fn look(colors: Colors, styles: &[Style]) -> Appearance {
+        Appearance::plain().with(&colors, styles)
+    }
+
+    #[test]
+    fn wrap_the_escape_sequences_for_bash() {
+        assert_eq!(
+            prompt(vec!(("user", look(Colors::new_fg(Color::green()), &[])), ("$ ", Appearance::plain())), Shell::Bash),
+            "\x01\x1b[32m\x02user\x01\x1b[0m\x02$ "
+        );
+    }
+
+    #[test]
+    fn wrap_the_escape_sequences_and_escape_the_percents_for_zsh() {
+        assert_eq!(
+            prompt(vec!(("100%", look(Colors::new_fg(Color::green()), &[]))), Shell::Zsh),
+            "%{\x1b[32m%}100%%%{\x1b[0m%}"
+        );
+    }
+
+    #[test]
+    fn use_the_native_sequences_of_zsh() {
+        assert_eq!(
+            prompt(
+                vec!(
+                    ("user", look(Colors::new(Color::red(), Color::rgb(1, 2, 3)), &[Strong, Underline])),
+                    ("@", look(Colors::new_fg(Color::red()), &[])),
+                ),
+                Shell::ZshNative,
+            ),
+            "%B%U%F{1}%K{#010203}user%b%u%k@%f"
+        );
+    }
+
+    #[test]
+    fn use_escape_sequences_for_what_zsh_cant_display() {
+        assert_eq!(
+            prompt(
+                vec!(("a", look(Colors::none(), &[Dim, Strong, Italic])), ("b", look(Colors::none(), &[Strong]))),
+                Shell::ZshNative,
+            ),
+            "%{\x1b[2;3m%}%Ba%{\x1b[22;23m%}%Bb%b"
+        );
+    }
+
+    #[test]
+    fn output_the_escape_sequences_as_they_are_for_fish() {
+        assert_eq!(
+            prompt(vec!(("fish", look(Colors::new_fg(Color::blue()), &[])), ("> ", Appearance::plain())), Shell::Fish),
+            "\x1b[34mfish\x1b[0m> "
+        );
+    }
+}
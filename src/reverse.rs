@@ -0,0 +1,192 @@
+use std::io::{BufRead, Write};
+
+use crate::ansi::AnsiParser;
+use crate::json::style_name;
+use crate::model::{Appearance, Color, Style};
+use crate::model::Color::{Byte, RGB};
+use crate::parser::parse_style;
+use crate::span::Span;
+
+/// Writes a cecho format for each line of the input, with the colors and styles of its ANSI escape sequences.
+///
+/// The styled text goes into quoted texts, like `{"error:" #r!s} disk full`, so that the formats take no argument.
+pub fn reverse_filter<R: BufRead, W: Write>(arguments: &[String], mut input: R, mut output: W) -> Result<(), String> {
+    if !arguments.is_empty() {
+        return Err("The reverse mode converts the standard input, it doesn't take any argument".to_string());
+    }
+
+    let mut parser = AnsiParser::default();
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        let read = input.read_until(b'\n', &mut line).map_err(|e| format!("Can't read the standard input: {}", e))?;
+
+        if read == 0 {
+            return Ok(());
+        }
+
+        let text = String::from_utf8_lossy(&line);
+        let (text, newline) = match text.strip_suffix('\n') {
+            Some(it) => (it, "\n"),
+            None => (text.as_ref(), ""),
+        };
+
+        let format = to_format(&parser.parse(text));
+
+        output.write_all(format!("{}{}", format, newline).as_bytes())
+            .and_then(|_| output.flush())
+            .map_err(|e| format!("Can't write the standard output: {}", e))?;
+    }
+}
+
+/// The format that outputs the spans.
+pub fn to_format(spans: &[Span]) -> String {
+    spans.iter()
+        .filter(|it| !it.text.is_empty())
+        .map(|span| {
+            if span.appearance.is_plain() {
+                escape(&span.text, &['\\', '{', '}'])
+            } else {
+                format!("{{\"{}\" {}}}", escape(&span.text, &['\\', '"']), specifier(&span.appearance))
+            }
+        })
+        .collect()
+}
+
+/// The colors and the styles of a specifier, like `#r/B!su!curly`.
+fn specifier(appearance: &Appearance) -> String {
+    let colors = &appearance.colors;
+    let mut layers: Vec<String> = [colors.foreground, colors.background, colors.underline].iter()
+        .map(|it| it.map(color_name).unwrap_or_default())
+        .collect();
+
+    while layers.last().is_some_and(|it| it.is_empty()) {
+        layers.pop();
+    }
+
+    let mut result = if layers.is_empty() { String::new() } else { format!("#{}", layers.join("/")) };
+
+    let (short, long): (Vec<Style>, Vec<Style>) = appearance.styles.iter().partition(|it| short_name(it).is_some());
+
+    if !short.is_empty() {
+        let letters: String = short.iter().filter_map(short_name).collect();
+
+        // the letters are written one by one when glued they would read as a style word
+        if parse_style(vec!(letters.clone())) == Ok(short) {
+            result.push_str(&format!("!{}", letters));
+        } else {
+            letters.chars().for_each(|it| result.push_str(&format!("!{}", it)));
+        }
+    }
+    // the styles without a letter, like `curly` or `font2`
+    for style in long.iter() {
+        result.push_str(&format!("!{}", style_name(style)));
+    }
+
+    result
+}
+
+/// The letters of the named colors, their index for the rest of the palette and the hexadecimal notation for the others.
+fn color_name(color: Color) -> String {
+    const LETTERS: [char; 8] = ['k', 'r', 'g', 'y', 'b', 'm', 'c', 'w'];
+
+    match color {
+        Byte(b) if b < 8 => LETTERS[b as usize].to_string(),
+        Byte(b) if b < 16 => LETTERS[b as usize - 8].to_ascii_uppercase().to_string(),
+        Byte(b) if b < 100 => b.to_string(),
+        // three digits would be read as a #rgb shorthand
+        Byte(b) => format!("c{}", b),
+        RGB { red, green, blue } => format!("{:02x}{:02x}{:02x}", red, green, blue),
+    }
+}
+
+fn short_name(style: &Style) -> Option<char> {
+    match style {
+        Style::Strong => Some('s'),
+        Style::Dim => Some('d'),
+        Style::Italic => Some('i'),
+        Style::Underline => Some('u'),
+        Style::Blink => Some('b'),
+        Style::Reversed => Some('r'),
+        Style::Hidden => Some('h'),
+        Style::CrossedOut => Some('c'),
+        Style::Overline => Some('o'),
+        Style::DoublyUnderlined => Some('w'),
+        Style::RapidBlink => Some('q'),
+        Style::Framed => Some('f'),
+        Style::Encircled => Some('e'),
+        Style::Superscript => Some('^'),
+        Style::Subscript => Some('_'),
+        _ => None,
+    }
+}
+
+/// Escapes the backslashes, the characters that the format would read, and the control characters that have a notation.
+fn escape(text: &str, special: &[char]) -> String {
+    let mut result = String::new();
+
+    for c in text.chars() {
+        match c {
+            '\x07' => result.push_str("\\a"),
+            '\x08' => result.push_str("\\b"),
+            '\t' => result.push_str("\\t"),
+            '\n' => result.push_str("\\n"),
+            '\x0b' => result.push_str("\\v"),
+            '\x0c' => result.push_str("\\f"),
+            '\r' => result.push_str("\\r"),
+            '\x1b' => result.push_str("\\e"),
+            _ if special.contains(&c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::parse_ansi;
+    use crate::cecho::cecho_with_config;
+    use crate::config::{ColorDepth, Config};
+    use crate::reverse::{reverse_filter, to_format};
+
+    fn reverse(text: &str) -> String {
+        to_format(&parse_ansi(text).spans)
+    }
+
+    #[test]
+    fn convert_the_colors_and_styles_to_specifiers() {
+        assert_eq!(reverse("\x1b[1;31merror:\x1b[0m disk full"), "{\"error:\" #r!s} disk full");
+        assert_eq!(reverse("\x1b[4:3;58;5;9;48;2;84;55;15mtypo\x1b[m"), "{\"typo\" #/54370f/R!curly}");
+        assert_eq!(reverse("\x1b[38;5;208;1;3;12mwarm"), "{\"warm\" #c208!si!font2}");
+    }
+
+    #[test]
+    fn escape_what_the_format_would_read() {
+        assert_eq!(reverse("{a}\t\\b\x1b[32m\"ok\"\x1b[0m"), "\\{a\\}\\t\\\\b{\"\\\"ok\\\"\" #g}");
+    }
+
+    #[test]
+    fn the_format_outputs_the_same_text() {
+        let config = Config { depth: ColorDepth::TrueColor, colored: true, ..Config::default() };
+
+        for ansi in ["a\x1b[1;38;2;1;2;3mb\x1b[22;4;44mc\x1b[24;7m{d}\x1b[0m", "\x1b[1;4;5mx\x1b[0m"] {
+            let format = reverse(ansi);
+            let output = cecho_with_config(vec!(format), &config).unwrap();
+
+            assert_eq!(reverse(&output), reverse(ansi));
+        }
+    }
+
+    #[test]
+    fn convert_the_lines_one_by_one() {
+        let mut output = Vec::new();
+        reverse_filter(&[], "\x1b[34mblue\nstill blue\x1b[0m\nplain".as_bytes(), &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "{\"blue\" #b}\n{\"still blue\" #b}\nplain");
+    }
+}
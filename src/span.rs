@@ -0,0 +1,177 @@
+use itertools::Itertools;
+
+use crate::config::Config;
+use crate::downsample::downsample;
+use crate::model::{Appearance, Colors, Part, Style, Text};
+use crate::model::Part::{Close, Literal, Open, Specification};
+use crate::model::Text::{AllArgs, Indexed, Positional};
+
+/// A piece of the output with what the terminal displays for it.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Span {
+    pub text: String,
+    pub appearance: Appearance,
+}
+
+/// The whole output, independently of how it is rendered.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Spans {
+    pub spans: Vec<Span>,
+    /// What stays displayed after the output, only the preserved specifiers leave something.
+    pub end: Appearance,
+}
+
+/// The spans of the texts with their appearance, that end with nothing displayed.
+#[cfg(test)]
+pub fn spans_of(spans: Vec<(&str, Appearance)>) -> Spans {
+    Spans {
+        spans: spans.into_iter().map(|(text, appearance)| Span { text: text.to_string(), appearance }).collect(),
+        end: Appearance::plain(),
+    }
+}
+
+/// Replaces the specifiers by their text and computes what each piece of text looks like.
+pub fn resolve(inputs: &[String], parts: Vec<Part>, config: &Config) -> Result<Spans, String> {
+    let mut position = 0;
+    // the number of the specifier in the format, starting at 1, like in the check mode
+    let mut number = 0;
+    let mut spans: Vec<Span> = Vec::new();
+    // what the open scopes display, the innermost one last,
+    // above what the preserved specifiers left outside any scope
+    let mut scopes: Vec<Appearance> = vec!(Appearance::plain());
+
+    for part in parts {
+        let current = scopes.last().cloned().unwrap_or(Appearance::plain());
+
+        if !matches!(part, Literal(_)) {
+            number += 1;
+        }
+
+        match part {
+            Literal(literal) => spans.push(Span { text: literal, appearance: current }),
+            Specification { text: selector, color, styles, preserve } => {
+                let (color, styles) = displayable(&color, &styles, config);
+                let displayed = current.with(&color, &styles);
+
+                let argument = |index: usize| inputs.get(index).cloned().ok_or_else(||
+                    format!("The specifier #{} refers to the missing argument {}", number, index)
+                );
+
+                let text = match selector {
+                    Indexed(i) => argument(i)?,
+                    AllArgs(separator) => inputs.iter().dropping(1).join(&separator),
+                    Positional => {
+                        position += 1;
+                        argument(position)?
+                    }
+                    Text::Literal(literal) => literal,
+                };
+
+                spans.push(Span { text, appearance: displayed.clone() });
+
+                // what is displayed stays until something else changes it
+                if preserve || config.preserve {
+                    if let Some(scope) = scopes.last_mut() {
+                        *scope = displayed;
+                    }
+                }
+            }
+            Open { color, styles } => {
+                let (color, styles) = displayable(&color, &styles, config);
+                scopes.push(current.with(&color, &styles));
+            }
+            Close { .. } => if scopes.len() > 1 {
+                scopes.pop();
+            }
+        }
+    }
+
+    Ok(Spans { spans, end: scopes.swap_remove(0) })
+}
+
+/// The colors and styles that the terminal can display.
+fn displayable(color: &Colors, styles: &[Style], config: &Config) -> (Colors, Vec<Style>) {
+    if config.colored {
+        let downsampled = Colors {
+            foreground: color.foreground.and_then(|it| downsample(it, config.depth)),
+            background: color.background.and_then(|it| downsample(it, config.depth)),
+            underline: color.underline.and_then(|it| downsample(it, config.depth)),
+        };
+        (downsampled, styles.to_vec())
+    } else {
+        (Colors::none(), vec!())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::model::{Appearance, Color, Colors, Part};
+    use crate::model::Part::Specification;
+    use crate::model::Style::Strong;
+    use crate::model::Text::Positional;
+    use crate::span::{resolve, Span, Spans};
+    use crate::vecs;
+
+    fn span(text: &str, appearance: Appearance) -> Span {
+        Span { text: text.to_string(), appearance }
+    }
+
+    fn red() -> Appearance {
+        Appearance::plain().with(&Colors::new_fg(Color::red()), &[])
+    }
+
+    #[test]
+    fn resolve_the_text_and_the_appearance_of_each_part() {
+        let parts = vec!(
+            Part::open(Colors::new_fg(Color::red()), vec!()),
+            Part::literal("error: "),
+            Part::positional_style(Strong),
+            Part::close(Colors::new_fg(Color::red()), vec!()),
+            Part::literal("!"),
+        );
+
+        let inputs = vecs!("unused", "disk full");
+
+        assert_eq!(
+            resolve(&inputs, parts, &Config::default()),
+            Ok(Spans {
+                spans: vec!(
+                    span("error: ", red()),
+                    span("disk full", red().with(&Colors::none(), &[Strong])),
+                    span("!", Appearance::plain()),
+                ),
+                end: Appearance::plain(),
+            })
+        );
+    }
+
+    #[test]
+    fn refuse_the_specifiers_that_refer_to_a_missing_argument() {
+        let inputs = vecs!("unused", "a");
+
+        assert_eq!(
+            resolve(&inputs, vec!(Part::positional(), Part::literal(" "), Part::positional()), &Config::default()),
+            Err("The specifier #2 refers to the missing argument 2".to_string())
+        );
+        assert_eq!(
+            resolve(&inputs, vec!(Part::indexed(3)), &Config::default()),
+            Err("The specifier #1 refers to the missing argument 3".to_string())
+        );
+    }
+
+    #[test]
+    fn the_preserved_appearance_outlives_the_spans() {
+        let parts = vec!(
+            Specification { text: Positional, color: Colors::new_fg(Color::red()), styles: vec!(), preserve: true },
+            Part::literal("."),
+        );
+
+        let inputs = vecs!("unused", "");
+
+        assert_eq!(
+            resolve(&inputs, parts, &Config::default()),
+            Ok(Spans { spans: vec!(span("", red()), span(".", red())), end: red() })
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use std::io::{BufRead, Write};
+use std::iter::Peekable;
+use std::str::Chars;
+
+pub const ESCAPE: char = '\x1b';
+const BELL: char = '\x07';
+
+/// Removes the ANSI escape sequences, colors and styles included, and keeps the text.
+pub fn strip_ansi(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == ESCAPE {
+            read_escape_sequence(&mut chars);
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Consumes an escape sequence, after its escape character.
+///
+/// Returns the parameters and the final byte of a control sequence, like `("1;31", 'm')`, and nothing for the others.
+pub fn read_escape_sequence(chars: &mut Peekable<Chars>) -> Option<(String, char)> {
+    match chars.next() {
+        // control sequence, like the colors: parameters and intermediate bytes, then a final byte
+        Some('[') => {
+            let mut parameters = String::new();
+            for it in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&it) {
+                    return Some((parameters, it));
+                }
+                parameters.push(it);
+            }
+        }
+        // operating system command, like the hyperlinks: up to a bell or a string terminator
+        Some(']' | 'P' | '_' | '^' | 'X') => {
+            while let Some(it) = chars.next() {
+                if it == BELL {
+                    break;
+                }
+                if it == ESCAPE && chars.peek() == Some(&'\\') {
+                    chars.next();
+                    break;
+                }
+            }
+        }
+        // character set selection takes one more character
+        Some('(' | ')' | '*' | '+') => {
+            chars.next();
+        }
+        // any other escape sequence is 2 characters long
+        _ => {}
+    }
+
+    None
+}
+
+/// Copies the input to the output without the ANSI escape sequences, line by line so that it works with pipes.
+pub fn strip_filter<R: BufRead, W: Write>(arguments: &[String], mut input: R, mut output: W) -> Result<(), String> {
+    if !arguments.is_empty() {
+        return Err("The strip mode filters the standard input, it doesn't take any argument".to_string());
+    }
+
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        let read = input.read_until(b'\n', &mut line).map_err(|e| format!("Can't read the standard input: {}", e))?;
+
+        if read == 0 {
+            return Ok(());
+        }
+
+        output.write_all(strip_ansi(&String::from_utf8_lossy(&line)).as_bytes())
+            .and_then(|_| output.flush())
+            .map_err(|e| format!("Can't write the standard output: {}", e))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::strip::{strip_ansi, strip_filter};
+
+    #[test]
+    fn strip_the_colors_and_styles() {
+        assert_eq!(strip_ansi("\x1b[1;31merror:\x1b[0m disk \x1b[38;2;1;2;3mfull\x1b[m"), "error: disk full");
+    }
+
+    #[test]
+    fn strip_the_other_escape_sequences() {
+        assert_eq!(strip_ansi("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07"), "link");
+        assert_eq!(strip_ansi("\x1b[2K\x1b(Bcleared\x1bc"), "cleared");
+    }
+
+    #[test]
+    fn keep_the_text_as_it_is() {
+        assert_eq!(strip_ansi("tabs\tand\nnew lines [ok]"), "tabs\tand\nnew lines [ok]");
+    }
+
+    #[test]
+    fn filter_line_by_line() {
+        let mut output = Vec::new();
+        strip_filter(&[], "\x1b[31mred\x1b[0m\nplain\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "red\nplain\n");
+    }
+
+    #[test]
+    fn the_filter_takes_no_argument() {
+        assert_eq!(
+            strip_filter(&["{}".to_string()], "".as_bytes(), Vec::new()),
+            Err("The strip mode filters the standard input, it doesn't take any argument".to_string())
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use crate::html::{css_color, escape};
+use crate::model::{Appearance, Color, Style};
+use crate::span::Spans;
+
+const FONT_SIZE: f64 = 14.0;
+/// The width of a character of a monospace font is about 60% of its size
+const CELL_WIDTH: f64 = 8.4;
+const LINE_HEIGHT: f64 = 18.0;
+/// Where the text sits in its line, from the top of the line
+const BASELINE: f64 = 14.0;
+const PADDING: f64 = 10.0;
+const TAB_WIDTH: usize = 8;
+
+/// A piece of text that fits on a single line of the grid.
+#[derive(PartialEq, Debug)]
+struct Piece {
+    row: usize,
+    column: usize,
+    text: String,
+    appearance: Appearance,
+}
+
+/// Outputs an SVG image of what a terminal would display, with the text in a monospace grid.
+///
+/// The terminal's default colors are the xterm ones, light gray on black.
+pub fn render_svg(spans: &Spans) -> String {
+    let (pieces, rows, columns) = layout(spans);
+
+    let width = 2.0 * PADDING + columns as f64 * CELL_WIDTH;
+    let height = 2.0 * PADDING + rows as f64 * LINE_HEIGHT;
+
+    let mut lines = vec!(
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"{}\" xml:space=\"preserve\">",
+            number(width), number(height), number(FONT_SIZE),
+        ),
+        format!("<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>", css_color(Color::black())),
+    );
+
+    for piece in pieces.iter() {
+        let x = PADDING + piece.column as f64 * CELL_WIDTH;
+        let y = PADDING + piece.row as f64 * LINE_HEIGHT;
+        let styles = &piece.appearance.styles;
+        let colors = &piece.appearance.colors;
+
+        let (foreground, background) = if styles.contains(&Style::Reversed) {
+            (colors.background.unwrap_or(Color::black()), Some(colors.foreground.unwrap_or(Color::white())))
+        } else {
+            (colors.foreground.unwrap_or(Color::white()), colors.background)
+        };
+
+        if let Some(background) = background {
+            lines.push(format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                number(x), number(y), number(piece.text.chars().count() as f64 * CELL_WIDTH), number(LINE_HEIGHT), css_color(background),
+            ));
+        }
+
+        if !styles.contains(&Style::Hidden) {
+            lines.push(format!(
+                "<text x=\"{}\" y=\"{}\" fill=\"{}\"{}>{}</text>",
+                number(x), number(y + BASELINE), css_color(foreground), attributes(styles), escape(&piece.text),
+            ));
+        }
+    }
+
+    lines.push("</svg>".to_string());
+    lines.join("\n")
+}
+
+/// Places the text in the grid, returns the pieces with the number of rows and columns that they take.
+fn layout(spans: &Spans) -> (Vec<Piece>, usize, usize) {
+    let mut pieces = Vec::new();
+    let mut row = 0;
+    let mut column = 0;
+    let mut columns = 0;
+
+    for span in spans.spans.iter() {
+        let mut text = String::new();
+        let mut start = column;
+
+        let mut flush = |text: &mut String, row: usize, start: usize| {
+            if !text.is_empty() {
+                pieces.push(Piece { row, column: start, text: text.clone(), appearance: span.appearance.clone() });
+                text.clear();
+            }
+        };
+
+        for c in span.text.chars() {
+            match c {
+                '\n' => {
+                    flush(&mut text, row, start);
+                    row += 1;
+                    column = 0;
+                    start = 0;
+                }
+                '\r' => {
+                    flush(&mut text, row, start);
+                    column = 0;
+                    start = 0;
+                }
+                '\t' => {
+                    let next = (column / TAB_WIDTH + 1) * TAB_WIDTH;
+                    text.push_str(&" ".repeat(next - column));
+                    column = next;
+                }
+                _ if c.is_control() => {}
+                _ => {
+                    text.push(c);
+                    column += 1;
+                }
+            }
+
+            columns = columns.max(column);
+        }
+
+        flush(&mut text, row, start);
+    }
+
+    // a trailing newline doesn't start another line
+    let rows = if column == 0 && row > 0 { row } else { row + 1 };
+
+    (pieces, rows, columns)
+}
+
+fn attributes(styles: &[Style]) -> String {
+    let mut attributes = String::new();
+    let has = |style: Style| styles.contains(&style);
+
+    if has(Style::Strong) {
+        attributes.push_str(" font-weight=\"bold\"");
+    }
+    if has(Style::Dim) {
+        attributes.push_str(" opacity=\"0.5\"");
+    }
+    if has(Style::Italic) {
+        attributes.push_str(" font-style=\"italic\"");
+    }
+    if has(Style::Superscript) {
+        attributes.push_str(" baseline-shift=\"super\"");
+    }
+    if has(Style::Subscript) {
+        attributes.push_str(" baseline-shift=\"sub\"");
+    }
+
+    let underlined = styles.iter().any(Style::is_underline);
+
+    let decorations: Vec<&str> = [
+        (underlined, "underline"),
+        (has(Style::Overline), "overline"),
+        (has(Style::CrossedOut), "line-through"),
+    ].iter().filter(|(present, _)| *present).map(|(_, decoration)| *decoration).collect();
+
+    if !decorations.is_empty() {
+        attributes.push_str(&format!(" text-decoration=\"{}\"", decorations.join(" ")));
+    }
+
+    attributes
+}
+
+/// At most 2 decimals, without the trailing zeros.
+fn number(value: f64) -> String {
+    format!("{:.2}", value).trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Appearance, Color, Colors};
+    use crate::model::Style::{Hidden, Reversed, Strong};
+    use crate::span::spans_of;
+    use crate::svg::{layout, render_svg, Piece};
+
+    fn piece(row: usize, column: usize, text: &str, appearance: Appearance) -> Piece {
+        Piece { row, column, text: text.to_string(), appearance }
+    }
+
+    #[test]
+    fn draw_the_text_with_its_colors_and_styles() {
+        let red_on_blue = Appearance::plain().with(&Colors::new(Color::red(), Color::blue()), &[Strong]);
+
+        assert_eq!(
+            render_svg(&spans_of(vec!(("ok ", Appearance::plain()), ("<no>", red_on_blue)))),
+            [
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"78.8\" height=\"38\" font-family=\"monospace\" font-size=\"14\" xml:space=\"preserve\">",
+                "<rect width=\"100%\" height=\"100%\" fill=\"#000000\"/>",
+                "<text x=\"10\" y=\"24\" fill=\"#e5e5e5\">ok </text>",
+                "<rect x=\"35.2\" y=\"10\" width=\"33.6\" height=\"18\" fill=\"#0000ee\"/>",
+                "<text x=\"35.2\" y=\"24\" fill=\"#cd0000\" font-weight=\"bold\">&lt;no&gt;</text>",
+                "</svg>",
+            ].join("\n")
+        );
+    }
+
+    #[test]
+    fn lay_out_the_lines_and_the_tabs_in_the_grid() {
+        let bold = Appearance::plain().with(&Colors::none(), &[Strong]);
+
+        assert_eq!(
+            layout(&spans_of(vec!(("a\tb\nc", Appearance::plain()), ("d\n", bold.clone())))),
+            (
+                vec!(
+                    piece(0, 0, "a       b", Appearance::plain()),
+                    piece(1, 0, "c", Appearance::plain()),
+                    piece(1, 1, "d", bold),
+                ),
+                2,
+                9,
+            )
+        );
+    }
+
+    #[test]
+    fn swap_the_colors_when_reversed_and_skip_the_hidden_text() {
+        let reversed = Appearance::plain().with(&Colors::new_fg(Color::red()), &[Reversed]);
+        let hidden = Appearance::plain().with(&Colors::none(), &[Hidden]);
+
+        let svg = render_svg(&spans_of(vec!(("r", reversed), ("h", hidden))));
+
+        assert!(svg.contains("<rect x=\"10\" y=\"10\" width=\"8.4\" height=\"18\" fill=\"#cd0000\"/>"));
+        assert!(svg.contains("<text x=\"10\" y=\"24\" fill=\"#000000\">r</text>"));
+        assert!(!svg.contains(">h</text>"));
+    }
+}
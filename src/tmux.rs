@@ -0,0 +1,201 @@
+use crate::config::Passthrough;
+use crate::html::css_color;
+use crate::model::{Appearance, Color, Style};
+use crate::span::Spans;
+
+const ESCAPE: char = '\x1b';
+const BELL: char = '\x07';
+
+/// Outputs the text with tmux's style sequences, like `#[fg=colour1,bold]`, for its status line.
+///
+/// Tmux has no superscript, subscript, fraktur, framing or alternative font, these styles are left out.
+pub fn render_tmux(spans: &Spans) -> String {
+    let mut result = String::new();
+    let mut displayed = Appearance::plain();
+
+    for span in spans.spans.iter().filter(|it| !it.text.is_empty()) {
+        result.push_str(&transition(&displayed, &span.appearance));
+        result.push_str(&span.text.replace('#', "##"));
+        displayed = span.appearance.clone();
+    }
+
+    result.push_str(&transition(&displayed, &spans.end));
+
+    result
+}
+
+fn transition(from: &Appearance, to: &Appearance) -> String {
+    let mut changes = Vec::new();
+
+    let colors = [
+        (from.colors.foreground, to.colors.foreground, "fg"),
+        (from.colors.background, to.colors.background, "bg"),
+        (from.colors.underline, to.colors.underline, "us"),
+    ];
+
+    for (before, after, name) in colors {
+        if before != after {
+            changes.push(format!("{}={}", name, after.map(tmux_color).unwrap_or("default".to_string())));
+        }
+    }
+
+    let (before, after) = (attributes(from), attributes(to));
+
+    for attribute in before.iter().filter(|it| !after.contains(it)) {
+        changes.push(format!("no{}", attribute));
+    }
+    for attribute in after.iter().filter(|it| !before.contains(it)) {
+        changes.push(attribute.to_string());
+    }
+
+    if changes.is_empty() {
+        String::new()
+    } else {
+        format!("#[{}]", changes.join(","))
+    }
+}
+
+fn tmux_color(color: Color) -> String {
+    match color {
+        Color::Byte(index) => format!("colour{}", index),
+        Color::RGB { .. } => css_color(color),
+    }
+}
+
+/// The names of tmux's attributes that display the styles.
+fn attributes(appearance: &Appearance) -> Vec<&'static str> {
+    let mut attributes = Vec::new();
+
+    for style in appearance.styles.iter() {
+        let attribute = match style {
+            Style::Strong => "bold",
+            Style::Dim => "dim",
+            Style::Italic => "italics",
+            Style::Underline => "underscore",
+            Style::DoubleUnderline | Style::DoublyUnderlined => "double-underscore",
+            Style::CurlyUnderline => "curly-underscore",
+            Style::DottedUnderline => "dotted-underscore",
+            Style::DashedUnderline => "dashed-underscore",
+            Style::Blink | Style::RapidBlink => "blink",
+            Style::Reversed => "reverse",
+            Style::Hidden => "hidden",
+            Style::CrossedOut => "strikethrough",
+            Style::Overline => "overline",
+            _ => continue,
+        };
+
+        if !attributes.contains(&attribute) {
+            attributes.push(attribute);
+        }
+    }
+
+    attributes
+}
+
+/// Wraps the operating system commands, like the hyperlinks or the window titles,
+/// so that tmux or screen passes them to the terminal instead of interpreting them.
+///
+/// The colors and the styles don't need it, the multiplexers display them by themselves.
+pub fn passthrough(text: &str, passthrough: Passthrough) -> String {
+    let start = match passthrough {
+        Passthrough::Off => return text.to_string(),
+        // the escape characters inside tmux's passthrough are doubled
+        Passthrough::Tmux => format!("{0}Ptmux;{0}{0}]", ESCAPE),
+        Passthrough::Screen => format!("{0}P{0}]", ESCAPE),
+    };
+
+    let mut result = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != ESCAPE || chars.peek() != Some(&']') {
+            result.push(c);
+            continue;
+        }
+
+        chars.next();
+        let mut command = String::new();
+        let mut terminated = false;
+
+        while let Some(it) = chars.next() {
+            if it == BELL {
+                terminated = true;
+                break;
+            }
+            if it == ESCAPE && chars.peek() == Some(&'\\') {
+                chars.next();
+                terminated = true;
+                break;
+            }
+            command.push(it);
+        }
+
+        if !terminated {
+            // an incomplete command is left as it is
+            result.push_str(&format!("{}]{}", ESCAPE, command));
+            continue;
+        }
+
+        // the multiplexers end their passthrough at the first string terminator, the command ends with a bell instead
+        result.push_str(&format!("{}{}{}{}\\", start, command, BELL, ESCAPE));
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Passthrough;
+    use crate::model::{Appearance, Color, Colors};
+    use crate::model::Style::{Blink, RapidBlink, Strong, Underline};
+    use crate::span::{spans_of, Spans};
+    use crate::tmux::{passthrough, render_tmux};
+
+    fn tmux(spans: Vec<(&str, Appearance)>) -> String {
+        render_tmux(&spans_of(spans))
+    }
+
+    #[test]
+    fn output_the_style_sequences_and_restore_them() {
+        let red_bold = Appearance::plain().with(&Colors::new(Color::red(), Color::rgb(1, 2, 3)), &[Strong]);
+
+        assert_eq!(
+            tmux(vec!(("#1", red_bold), (" 99%", Appearance::plain()))),
+            "#[fg=colour1,bg=#010203,bold]##1#[fg=default,bg=default,nobold] 99%"
+        );
+    }
+
+    #[test]
+    fn only_output_what_changes() {
+        let red = Appearance::plain().with(&Colors::new_fg(Color::red()), &[]);
+        let red_underlined = red.with(&Colors::new_underline(Color::blue()), &[Underline, Blink, RapidBlink]);
+
+        assert_eq!(
+            tmux(vec!(("a", red.clone()), ("b", red_underlined), ("c", red))),
+            "#[fg=colour1]a#[us=colour4,underscore,blink]b#[us=default,nounderscore,noblink]c#[fg=default]"
+        );
+    }
+
+    #[test]
+    fn keep_what_the_preserved_specifiers_leave() {
+        let red = Appearance::plain().with(&Colors::new_fg(Color::red()), &[]);
+        let spans = Spans { end: red.clone(), ..spans_of(vec!(("a", red))) };
+
+        assert_eq!(render_tmux(&spans), "#[fg=colour1]a");
+    }
+
+    #[test]
+    fn wrap_the_operating_system_commands() {
+        let link = "\x1b[1m\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07\x1b[0m";
+
+        assert_eq!(
+            passthrough(link, Passthrough::Tmux),
+            "\x1b[1m\x1bPtmux;\x1b\x1b]8;;https://example.com\x07\x1b\\link\x1bPtmux;\x1b\x1b]8;;\x07\x1b\\\x1b[0m"
+        );
+        assert_eq!(
+            passthrough(link, Passthrough::Screen),
+            "\x1b[1m\x1bP\x1b]8;;https://example.com\x07\x1b\\link\x1bP\x1b]8;;\x07\x1b\\\x1b[0m"
+        );
+        assert_eq!(passthrough(link, Passthrough::Off), link);
+    }
+}
@@ -1,138 +1,149 @@
-use itertools::{Itertools, join};
-use regex::Replacer;
-
-use crate::model::Part;
-use crate::model::Part::{Literal, Specification};
-use crate::model::Text::{AllArgs, Indexed, Positional};
-
-pub fn spec_to_ansi(inputs: &[String], specs: Vec<Part>) -> Result<String, String> {
-    let mut position = 0;
-    let mut result = specs.iter().map(|spec|
-        match spec {
-            Literal(literal) => literal.to_string(),
-            Specification { text: selector, color, styles: style } => {
-                let mut pre = String::new();
-                let mut post = String::new();
-
-                // prepare to add color or style
-                if !style.is_empty() || color.foreground.is_some() {
-                    pre.push_str("\x1b[");
-                }
-
-                // reset the color and style
-                if !style.is_empty() || color.foreground.is_some() || color.background.is_some() {
-                    post.push_str("\x1b[0m")
-                }
-
-                style.iter().for_each(|s|
-                    pre.push_str((*s as i32).to_string().as_str())
-                );
-
-                color.foreground.as_ref().map(|fg| {
-                    if !style.is_empty() {
-                        pre.push(';');
-                    }
-                    pre.push_str(&fg.escape_code());
-                });
-
-                if !style.is_empty() || color.foreground.is_some() {
-                    pre.push('m');
-                }
-
-                color.background.as_ref().map(|fg| {
-                    pre.push_str("\x1b[");
-                    let c = fg.as_ansi_background_escape_code();
-                    pre.push_str(&c);
-                    pre.push_str("m");
-                });
-
-                let mut text = String::new();
-
-                match selector {
-                    Indexed(i) => {
-                        text = text + &inputs[*i];
-                    }
-                    AllArgs(sep) => {
-                        text = text + &inputs.iter().dropping(1).join(sep);
-                    }
-                    Positional => {
-                        position += 1;
-                        text = text + &inputs[position];
-                    }
-                };
-
-                let mut full = String::new();
-                full.push_str(&pre);
-                full.push_str(text.as_str());
-                full.push_str(&post);
-
-                full
-            }
+use crate::model::{Appearance, Color};
+use crate::span::Spans;
+
+/// Outputs the text with only the escape sequences that change what the terminal displays.
+pub fn render(spans: &Spans) -> String {
+    let mut result = String::new();
+    let mut displayed = Appearance::plain();
+
+    for span in spans.spans.iter().filter(|it| !it.text.is_empty()) {
+        result.push_str(&sgr_transition(&displayed, &span.appearance));
+        result.push_str(&span.text);
+        displayed = span.appearance.clone();
+    }
+
+    result.push_str(&sgr_transition(&displayed, &spans.end));
+
+    result
+}
+
+/// The shortest escape sequence that goes from an appearance to another one.
+///
+/// Either turns off what is not displayed anymore and turns on what is new,
+/// or resets everything and turns on all that must be displayed.
+pub fn sgr_transition(from: &Appearance, to: &Appearance) -> String {
+    let diff = diff_codes(from, to);
+
+    if diff.is_empty() {
+        return String::new();
+    }
+
+    let reset: Vec<String> = std::iter::once("0".to_string()).chain(on_codes(to)).collect();
+    let shortest = if diff.join(";").len() <= reset.join(";").len() { diff } else { reset };
+
+    format!("\x1b[{}m", shortest.join(";"))
+}
+
+/// The codes that turn off what is not displayed anymore and turn on what is new.
+pub fn diff_codes(from: &Appearance, to: &Appearance) -> Vec<String> {
+    let mut off: Vec<&str> = Vec::new();
+    from.styles.iter()
+        .filter(|it| !to.styles.contains(it))
+        .filter_map(|it| it.off_code())
+        .for_each(|it| if !off.contains(&it) { off.push(it) });
+
+    // some styles share their off code, like bold and dim, so they must be turned on again
+    let on = to.styles.iter()
+        .filter(|it| !from.styles.contains(it) || it.off_code().is_some_and(|code| off.contains(&code)))
+        .map(|it| it.code());
+
+    let mut codes: Vec<String> = off.iter().map(|it| it.to_string()).chain(on).collect();
+
+    for (before, after, reset, code) in color_layers(from, to) {
+        if before != after {
+            codes.push(after.as_ref().map(code).unwrap_or(reset.to_string()));
         }
-    ).join("");
+    }
+
+    codes
+}
+
+/// The codes that turn on everything from a plain appearance.
+pub fn on_codes(appearance: &Appearance) -> Vec<String> {
+    let colors = color_layers(&Appearance::plain(), appearance)
+        .into_iter()
+        .filter_map(|(_, color, _, code)| color.as_ref().map(code));
+
+    appearance.styles.iter().map(|it| it.code()).chain(colors).collect()
+}
 
-    result.push_str("\x1b[0m");
+/// A color before and after a transition, with the codes to reset and to set it.
+type ColorLayer = (Option<Color>, Option<Color>, &'static str, fn(&Color) -> String);
 
-    Ok(result)
+/// The foreground, background and underline colors of both appearances.
+fn color_layers(from: &Appearance, to: &Appearance) -> [ColorLayer; 3] {
+    [
+        (from.colors.foreground, to.colors.foreground, "39", Color::escape_code),
+        (from.colors.background, to.colors.background, "49", Color::as_ansi_background_escape_code),
+        (from.colors.underline, to.colors.underline, "59", Color::as_ansi_underline_escape_code),
+    ]
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::config::{ColorDepth, Config};
     use crate::model::{Color, Colors, Part};
-    use crate::model::Style::{Blink, Strong};
-    use crate::model::Text::AllArgs;
+    use crate::model::Part::Specification;
+    use crate::model::Text::Positional;
+    use crate::model::Style::{Absent, AlternativeFont, Blink, CurlyUnderline, Dim, Italic, Overline, Strong, Superscript};
     use crate::vecs;
-    use crate::writer::spec_to_ansi;
+    use crate::span::resolve;
+    use crate::writer::render;
 
-    fn test_ok_spec_to_ansi(mut inputs: Vec<String>, parts: Vec<Part>, expected: &str) {
+    fn test_ok_render(mut inputs: Vec<String>, parts: Vec<Part>, expected: &str) {
         inputs.insert(0, "unused but necessary because this is the place of the formatter".to_string());
-        let result = spec_to_ansi(&inputs, parts);
-        let ok = result.unwrap();
-        assert_eq!(ok, expected);
+        let spans = resolve(&inputs, parts, &Config::default()).unwrap();
+        assert_eq!(render(&spans), expected);
+    }
+
+    fn test_ok_render_with_depth(depth: ColorDepth, parts: Vec<Part>, expected: &str) {
+        let inputs = vecs!("unused", "text");
+        let spans = resolve(&inputs, parts, &Config { depth, ..Config::default() }).unwrap();
+        assert_eq!(render(&spans), expected);
     }
 
     #[test]
-    fn always_reset_the_style() {
-        test_ok_spec_to_ansi(
+    fn no_escape_sequence_for_plain_text() {
+        test_ok_render(
             vecs!("default"),
             vec!(
                 Part::literal("##"),
                 Part::literal("default"),
                 Part::literal("##"),
             ),
-            "##default##\x1b[0m",
+            "##default##",
         )
     }
 
     #[test]
     fn output_escape_sequence_for_red_string_surrounded_by_hashes() {
-        test_ok_spec_to_ansi(
+        test_ok_render(
             vecs!("red"),
             vec!(
                 Part::literal("##"),
                 Part::indexed_color(1, Colors::new_fg(Color::red())),
                 Part::literal("##"),
             ),
-            "##\x1b[31mred\x1b[0m##\x1b[0m",
+            "##\x1b[31mred\x1b[0m##",
         )
     }
 
     #[test]
     fn output_escape_sequence_for_green_string_surrounded_by_hashes() {
-        test_ok_spec_to_ansi(
+        test_ok_render(
             vecs!("green"),
             vec!(
                 Part::literal("##"),
                 Part::indexed_color(1, Colors::new_fg(Color::green())),
                 Part::literal("##"),
             ),
-            "##\x1b[32mgreen\x1b[0m##\x1b[0m",
+            "##\x1b[32mgreen\x1b[0m##",
         )
     }
 
     #[test]
     fn output_escape_sequence_for_yellow_on_red() {
-        test_ok_spec_to_ansi(
+        test_ok_render(
             vecs!("DANGER"),
             vec!(
                 Part::literal("##"),
@@ -145,85 +156,279 @@ mod tests {
                 ),
                 Part::literal("##"),
             ),
-            "##\x1b[33m\x1b[41mDANGER\x1b[0m##\x1b[0m",
+            "##\x1b[33;41mDANGER\x1b[0m##",
         );
     }
 
     #[test]
     fn output_bold_style_option_in_ansi_sequence() {
-        test_ok_spec_to_ansi(
+        test_ok_render(
             vecs!("Bald"),
             vec!(
                 Part::literal("##"),
                 Part::positional_style(Strong),
                 Part::literal("##"),
             ),
-            "##\x1b[1mBald\x1b[0m##\x1b[0m",
+            "##\x1b[1mBald\x1b[0m##",
         );
     }
 
     #[test]
     fn output_blink_style_option_in_ansi_sequence() {
-        test_ok_spec_to_ansi(
+        test_ok_render(
             vecs!("TikTok"),
             vec!(
                 Part::literal("##"),
                 Part::positional_style(Blink),
                 Part::literal("##"),
             ),
-            "##\x1b[5mTikTok\x1b[0m##\x1b[0m",
+            "##\x1b[5mTikTok\x1b[0m##",
+        );
+    }
+
+    #[test]
+    fn output_bright_colors_in_the_90_and_100_ranges() {
+        test_ok_render(
+            vecs!("Bright"),
+            vec!(
+                Part::indexed_color(1, Colors::new(Color::bright_red(), Color::bright_white())),
+            ),
+            "\x1b[91;107mBright\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn output_256_colors_palette() {
+        test_ok_render(
+            vecs!("Orange"),
+            vec!(
+                Part::indexed_color(1, Colors::new(Color::palette(208), Color::gray(3))),
+            ),
+            "\x1b[38;5;208;48;5;235mOrange\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn separate_the_styles_with_semicolons() {
+        test_ok_render(
+            vecs!("Both"),
+            vec!(Part::positional_styles(vec!(Strong, Italic))),
+            "\x1b[1;3mBoth\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn output_the_less_common_styles() {
+        test_ok_render(
+            vecs!("x"),
+            vec!(Part::positional_styles(vec!(Overline, Superscript, AlternativeFont(2)))),
+            "\x1b[53;73;12mx\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn output_curly_underline_with_underline_color() {
+        test_ok_render(
+            vecs!("typo"),
+            vec!(
+                Specification {
+                    text: Positional,
+                    color: Colors::new_underline(Color::rgb(255, 0, 0)),
+                    styles: vec!(CurlyUnderline),
+                    preserve: false,
+                },
+            ),
+            "\x1b[4:3;58;2;255;0;0mtypo\x1b[0m",
         );
     }
 
     #[test]
     fn output_rgb_color_brown() {
-        test_ok_spec_to_ansi(
+        test_ok_render(
             vecs!("Poop"),
             vec!(
                 Part::literal("##"),
                 Part::positional_color(Color::rgb(84, 55, 15)),
                 Part::literal("##"),
             ),
-            "##\x1b[38;2;84;55;15mPoop\x1b[0m##\x1b[0m",
+            "##\x1b[38;2;84;55;15mPoop\x1b[0m##",
         );
     }
 
     #[test]
     fn output_rgb_background_color_brown() {
-        test_ok_spec_to_ansi(
+        test_ok_render(
             vecs!("Poop"),
             vec!(
                 Part::literal("##"),
                 Part::positional_background_color(Color::rgb(84, 55, 15)),
                 Part::literal("##"),
             ),
-            "##\x1b[48;2;84;55;15mPoop\x1b[0m##\x1b[0m",
+            "##\x1b[48;2;84;55;15mPoop\x1b[0m##",
+        );
+    }
+
+    #[test]
+    fn downsample_rgb_colors_to_the_256_colors_palette() {
+        test_ok_render_with_depth(
+            ColorDepth::Palette256,
+            vec!(Part::indexed_color(1, Colors::new(Color::rgb(255, 135, 0), Color::rgb(128, 128, 128)))),
+            "\x1b[38;5;208;48;5;244mtext\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn downsample_colors_to_the_16_named_colors() {
+        test_ok_render_with_depth(
+            ColorDepth::Ansi16,
+            vec!(Part::indexed_color(1, Colors::new(Color::rgb(255, 0, 0), Color::palette(16)))),
+            "\x1b[91;40mtext\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn keep_the_styles_but_not_the_colors_in_monochrome() {
+        test_ok_render_with_depth(
+            ColorDepth::Monochrome,
+            vec!(
+                Part::indexed_color(1, Colors::new_fg(Color::red())),
+                Part::positional_style(Strong),
+            ),
+            "text\x1b[1mtext\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn output_plain_text_when_the_colors_are_disabled() {
+        let inputs = vecs!("unused", "a", "b");
+        let parts = vec!(
+            Part::literal("["),
+            Part::positional_color(Color::red()),
+            Part::literal("|"),
+            Part::positional_style(Strong),
+            Part::literal("]"),
+        );
+        let spans = resolve(&inputs, parts, &Config { colored: false, ..Config::default() }).unwrap();
+        assert_eq!(render(&spans), "[a|b]");
+    }
+
+    #[test]
+    fn close_the_scopes_with_the_off_codes() {
+        test_ok_render(
+            vecs!("disk full"),
+            vec!(
+                Part::open(Colors::new_fg(Color::red()), vec!()),
+                Part::literal("error: "),
+                Part::open(Colors::none(), vec!(Strong)),
+                Part::indexed(1),
+                Part::close(Colors::none(), vec!(Strong)),
+                Part::literal(" happened"),
+                Part::close(Colors::new_fg(Color::red()), vec!()),
+            ),
+            "\x1b[31merror: \x1b[1mdisk full\x1b[22m happened\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn go_back_to_the_scope_after_a_specifier() {
+        test_ok_render(
+            vecs!("blue"),
+            vec!(
+                Part::open(Colors::new(Color::red(), Color::black()), vec!()),
+                Part::literal("<"),
+                Part::positional_color(Color::blue()),
+                Part::literal(">"),
+                Part::close(Colors::new(Color::red(), Color::black()), vec!()),
+            ),
+            "\x1b[31;40m<\x1b[34mblue\x1b[31m>\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn turn_on_again_the_styles_that_share_an_off_code() {
+        test_ok_render(
+            vecs!("bold"),
+            vec!(
+                Part::open(Colors::new_fg(Color::rgb(84, 55, 15)), vec!(Dim)),
+                Part::literal("dim "),
+                Part::positional_style(Strong),
+                Part::literal(" dim"),
+                Part::close(Colors::new_fg(Color::rgb(84, 55, 15)), vec!(Dim)),
+            ),
+            "\x1b[2;38;2;84;55;15mdim \x1b[1mbold\x1b[22;2m dim\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn merge_the_adjacent_specifiers_that_look_the_same() {
+        test_ok_render(
+            vecs!("a", "b", "c"),
+            vec!(
+                Part::positional_color(Color::red()),
+                Part::positional_color(Color::red()),
+                Part::positional_styles(vec!(Strong)),
+            ),
+            "\x1b[31mab\x1b[0;1mc\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn keep_the_preserved_colors_and_styles() {
+        test_ok_render(
+            vecs!("red", "bold"),
+            vec!(
+                Specification { text: Positional, color: Colors::new_fg(Color::red()), styles: vec!(), preserve: true },
+                Part::literal(" and "),
+                Part::positional_style(Strong),
+                Part::literal(" and more"),
+            ),
+            "\x1b[31mred and \x1b[1mbold\x1b[22m and more",
+        );
+    }
+
+    #[test]
+    fn reset_the_preserved_colors_and_styles() {
+        test_ok_render(
+            vecs!("red", "plain"),
+            vec!(
+                Specification { text: Positional, color: Colors::new_fg(Color::red()), styles: vec!(), preserve: true },
+                Specification { text: Positional, color: Colors::none(), styles: vec!(Absent), preserve: true },
+            ),
+            "\x1b[31mred\x1b[0mplain",
         );
     }
 
+    #[test]
+    fn preserve_everything_in_the_global_mode() {
+        let inputs = vecs!("unused", "a", "b");
+        let parts = vec!(Part::positional_color(Color::red()), Part::positional_style(Strong));
+        let spans = resolve(&inputs, parts, &Config { preserve: true, ..Config::default() }).unwrap();
+        assert_eq!(render(&spans), "\x1b[31ma\x1b[1mb");
+    }
+
     #[test]
     fn output_all_the_inputs() {
-        test_ok_spec_to_ansi(
+        test_ok_render(
             vecs!("a", "b", "c"),
             vec!(
                 Part::literal("##"),
                 Part::all_args(),
                 Part::literal("##"),
             ),
-            "##a b c##\x1b[0m",
+            "##a b c##",
         );
     }
 
     #[test]
     fn output_all_the_inputs_with_custom_separator() {
-        test_ok_spec_to_ansi(
+        test_ok_render(
             vecs!("a", "b", "c"),
             vec!(
                 Part::literal("##"),
                 Part::all_args_custom_separator("|"),
                 Part::literal("##"),
             ),
-            "##a|b|c##\x1b[0m",
+            "##a|b|c##",
         );
     }
 }